@@ -0,0 +1,76 @@
+//! Per-frame cost of extending a drawn line, run with `cargo bench --bench stroke_colliders`.
+//!
+//! Drawing used to rebuild the whole line as a compound of balls on every mouse move,
+//! now each move only builds the capsule for the new segment. The first two columns only build
+//! the collider of one move, the old way and the new one; `extend step` is a whole headless update
+//! handling one move: the path rebuilt for rendering, the capsule inserted into Rapier and the
+//! physics step with the line's colliders in it.
+
+use std::{hint::black_box, time::{Duration, Instant}};
+
+use bevy::prelude::{default, Vec2};
+use bevy_rapier2d::prelude::Collider;
+use moon::{
+    input::DrawInput,
+    level::Level,
+    player::{line_segment_collider, Lines, PLAYER_DRAW_LINE_WIDTH, PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD},
+    sim::Simulation,
+};
+
+const FRAMES: u32 = 200;
+
+fn stroke(len: usize) -> Vec<Vec2> {
+    (0..len)
+        .map(|i| {
+            // a little over the threshold, so f32 rounding far along doesn't drop moves
+            let t = i as f32 * PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD * 1.1;
+            Vec2::new(t, (t * 0.05).sin() * 50.)
+        })
+        .collect()
+}
+
+fn per_frame(mut frame: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame();
+    }
+    start.elapsed() / FRAMES
+}
+
+// a headless game in the middle of drawing the first `len` vertices of `vertices`
+fn drawing(vertices: &[Vec2], len: usize) -> Simulation {
+    // no ink budget, the ball well away from the line
+    let mut sim = Simulation::new(vec![Level { player: Vec2::new(0., 500.), ..default() }]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.draw(DrawInput::Begin(vertices[0]));
+    sim.tick();
+    for v in &vertices[1..len] {
+        sim.draw(DrawInput::Extend(*v));
+        sim.tick();
+    }
+    sim
+}
+
+fn main() {
+    println!("{:>8} {:>16} {:>16} {:>16}", "vertices", "ball compound", "capsule segment", "extend step");
+    for len in [100, 500, 1000, 2000, 5000] {
+        let vertices = stroke(len + FRAMES as usize);
+        let mut sim = drawing(&vertices, len);
+        let mut next = vertices[len..].iter();
+        let step = per_frame(|| {
+            sim.draw(DrawInput::Extend(*next.next().unwrap()));
+            sim.tick();
+        });
+        let drawn = sim.app.world.resource::<Lines>().0.values().map(Vec::len).sum::<usize>();
+        assert_eq!(drawn, len + FRAMES as usize, "a move wasn't drawn");
+        let compound = per_frame(|| {
+            black_box(Collider::compound(vertices[..len].iter()
+                .map(|v| (*v, 0., Collider::ball(PLAYER_DRAW_LINE_WIDTH / 2.0)))
+                .collect::<Vec<_>>()));
+        });
+        let segment = per_frame(|| {
+            black_box(line_segment_collider(vertices[len - 2], vertices[len - 1], PLAYER_DRAW_LINE_WIDTH));
+        });
+        println!("{:>8} {:>16?} {:>16?} {:>16?}", len, compound, segment, step);
+    }
+}
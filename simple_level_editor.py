@@ -182,7 +182,7 @@ if __name__ == "__main__":
         Esc: Exit the program
     ''')
     filename = input("Enter the name of the level: ")
-    filepath = f"./src/levels/{filename}.txt"
+    filepath = f"./assets/levels/{filename}.txt"
 
     pygame.init()
     screen = pygame.display.set_mode((WIDTH, HEIGHT))
@@ -0,0 +1,118 @@
+//! Checks that levels can be finished, run with `cargo run --bin moon-verify [level files...]`.
+//!
+//! Without arguments every level in the manifest is checked. Each level is played headless with
+//! a few simple candidate stroke sets, straight ramps from the spawn toward the stars and goals,
+//! and the first set that finishes the level is printed as `l` records.
+
+use std::{path::{Path, PathBuf}, process::ExitCode};
+
+use bevy::prelude::*;
+use moon::{
+    GameState,
+    format::LevelFormat,
+    ink::polyline_length,
+    level::{Level, LEVELS_FOLDER},
+    manifest::{LevelManifest, LEVEL_MANIFEST_FILE},
+    player::{PLAYER_RADIUS, PLAYER_DRAW_LINE_WIDTH},
+    sim::Simulation,
+};
+
+// generous, the load animation shows one entity at a time
+const LOAD_TICKS: u32 = 60 * 30;
+const SOLVE_TICKS: u32 = 60 * 20;
+
+fn level_files() -> Result<Vec<PathBuf>, String> {
+    let args = std::env::args().skip(1).map(PathBuf::from).collect::<Vec<_>>();
+    if !args.is_empty() {
+        return Ok(args);
+    }
+    let manifest_path = Path::new("assets").join(LEVEL_MANIFEST_FILE);
+    let text = std::fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+    let manifest = ron::from_str::<LevelManifest>(&text)
+        .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+    Ok(manifest.levels.iter().map(|entry| Path::new("assets").join(LEVELS_FOLDER).join(&entry.file)).collect())
+}
+
+// a stroke the ball rolls along from resting on its start to passing through `to`
+fn ramp(from: Vec2, to: Vec2) -> Vec<Vec2> {
+    let below = Vec2::new(0., PLAYER_RADIUS + PLAYER_DRAW_LINE_WIDTH / 2.);
+    vec![from - below, to - below]
+}
+
+fn candidates(level: &Level) -> Vec<Vec<Vec<Vec2>>> {
+    let targets = level.stars.iter().chain(level.goals.iter()).copied().collect::<Vec<_>>();
+    let mut candidates = vec![Vec::new()];
+    for target in targets.iter().filter(|t| t.y < level.player.y) {
+        candidates.push(vec![ramp(level.player, *target)]);
+    }
+    // one stroke down through every target, stars before goals
+    let mut chain = level.stars.clone();
+    chain.sort_by(|a, b| b.y.total_cmp(&a.y));
+    chain.extend(level.goals.iter().copied());
+    if chain.len() > 1 {
+        let below = Vec2::new(0., PLAYER_RADIUS + PLAYER_DRAW_LINE_WIDTH / 2.);
+        let stroke = std::iter::once(level.player).chain(chain).map(|v| v - below).collect();
+        candidates.push(vec![stroke]);
+    }
+    if let Some(ink) = level.ink {
+        candidates.retain(|strokes| strokes.iter().map(|s| polyline_length(s)).sum::<f32>() <= ink);
+    }
+    candidates
+}
+
+fn finishes(level: &Level, strokes: &[Vec<Vec2>]) -> bool {
+    let mut sim = Simulation::new(vec![level.clone()]);
+    if sim.wait_for_playing(LOAD_TICKS).is_none() {
+        return false;
+    }
+    for stroke in strokes {
+        sim.add_line(stroke);
+    }
+    sim.release_gravity();
+    sim.run_until(SOLVE_TICKS, |sim| matches!(sim.state(), GameState::Cleaning | GameState::Failed));
+    sim.state() == GameState::Cleaning
+}
+
+fn stroke_record(stroke: &[Vec2]) -> String {
+    let vertices = stroke.iter().map(|v| format!("{},{}", v.x, v.y)).collect::<Vec<_>>();
+    format!("l {}", vertices.join(" "))
+}
+
+fn main() -> ExitCode {
+    let files = match level_files() {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut all_solvable = true;
+    for path in files {
+        let level = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Level::read(&text, LevelFormat::from_path(&path)).map_err(|e| e.to_string()));
+        let level = match level {
+            Ok(level) => level,
+            Err(e) => {
+                println!("✗ {}: {}", path.display(), e);
+                all_solvable = false;
+                continue;
+            }
+        };
+        let candidates = candidates(&level);
+        match candidates.iter().find(|strokes| finishes(&level, strokes)) {
+            Some(strokes) => {
+                println!("✓ {}: solvable with {} strokes", path.display(), strokes.len());
+                for stroke in strokes {
+                    println!("    {}", stroke_record(stroke));
+                }
+            }
+            None => {
+                println!("✗ {}: no solution among {} candidates", path.display(), candidates.len());
+                all_solvable = false;
+            }
+        }
+    }
+    if all_solvable { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use crate::{GameLevels, LevelState, MainCamera, PlayerStatus, RenderInterpolation, interpolation_alpha, logical_size};
+
+/// How the camera follows the ball.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraFollow {
+    /// half extents of the area around the screen center the ball moves in without scrolling
+    pub dead_zone: Vec2,
+    /// how fast the camera catches up, higher is snappier, `0` jumps straight there
+    pub smoothing: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        CameraFollow {
+            dead_zone: Vec2::new(160., 90.),
+            smoothing: 5.,
+        }
+    }
+}
+
+// closest camera position to `target` that keeps the view inside the level
+fn clamp_to_level(target: Vec2, all_levels: &GameLevels, level_state: &LevelState) -> Vec2 {
+    let Some(level) = all_levels.get(level_state.id) else { return target; };
+    let bounds = level.world_bounds();
+    let half_view = logical_size() / 2.;
+    let (min, max) = (bounds.min + half_view, bounds.max - half_view);
+    // bounds smaller than the screen stay centered
+    let center = (bounds.min + bounds.max) / 2.;
+    Vec2::new(
+        if min.x <= max.x { target.x.clamp(min.x, max.x) } else { center.x },
+        if min.y <= max.y { target.y.clamp(min.y, max.y) } else { center.y },
+    )
+}
+
+pub fn reset_camera(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(level) = all_levels.get(level_state.id) else { return; };
+    let position = clamp_to_level(level.player, &all_levels, &level_state);
+    for mut transform in q_camera.iter_mut() {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+pub fn follow_player(
+    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
+    follow: Res<CameraFollow>,
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    q_player: Query<&RenderInterpolation, With<PlayerStatus>>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Ok(player) = q_player.get_single() else { return; };
+    // where the ball is drawn, following it between steps would judder
+    let ball = player.at(interpolation_alpha(&fixed_time));
+    for mut transform in q_camera.iter_mut() {
+        let position = transform.translation.truncate();
+        let offset = ball - position;
+        let outside = (offset.abs() - follow.dead_zone).max(Vec2::ZERO);
+        let target = clamp_to_level(position + outside * offset.signum(), &all_levels, &level_state);
+        let t = if follow.smoothing > 0. { 1. - (-follow.smoothing * time.delta_seconds()).exp() } else { 1. };
+        let position = position.lerp(target, t);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    GameState, GameLevels, Level, LevelState, LevelFormat, LEVELS_FOLDER,
+    MainCamera, polyline_path, cursor_to_world,
+    LEVEL_SCENE_LINE_WIDTH, STAR_RADIUS, SCENE_Z_INDEX, PLAYER_RADIUS, PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD,
+    HAZARD_COLOR, BOUNCY_COLOR, GOAL_RADIUS, HINT_FONT_SIZE,
+};
+
+pub static EDITOR_TOGGLE_KEY: KeyCode = KeyCode::Tab;
+pub static EDITOR_HELP_FONT_SIZE: f32 = 18.;
+pub static EDITOR_HELP: &str = "Left: draw line | Right: star | Middle: player | Z/X: undo line/star | Ctrl+S: save | Tab: play-test";
+
+/// The level being edited, written back to [`GameLevels`] on play-test.
+#[derive(Debug, Resource, Default, Clone)]
+pub struct EditorLevel(pub Level);
+
+/// Polyline currently drawn with the mouse, not part of the level yet.
+#[derive(Debug, Resource, Default, Clone)]
+pub struct EditorStroke(pub Option<Vec<Vec2>>);
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct EditorEntity;
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct EditorStrokeEntity;
+
+type AnyEditorEntity = Or<(With<EditorEntity>, With<EditorStrokeEntity>)>;
+
+pub fn open_editor(
+    keyboard: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(EDITOR_TOGGLE_KEY) {
+        info!("Entering level editor");
+        next_state.set(GameState::Editing);
+    }
+}
+
+pub fn enter_editor(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut editor_stroke: ResMut<EditorStroke>,
+    mut commands: Commands,
+) {
+    editor_level.0 = all_levels.get(level_state.id).cloned().unwrap_or_default();
+    editor_stroke.0 = None;
+    commands.spawn((
+        TextBundle::from_section(EDITOR_HELP, TextStyle {
+            font_size: EDITOR_HELP_FONT_SIZE,
+            color: Color::BLACK,
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            bottom: Val::Px(10.),
+            ..default()
+        }),
+        EditorEntity,
+    ));
+}
+
+pub fn exit_editor(
+    mut commands: Commands,
+    q_editor: Query<Entity, AnyEditorEntity>,
+) {
+    for entity in q_editor.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn edit_level(
+    buttons: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut editor_stroke: ResMut<EditorStroke>,
+) {
+    if keyboard.just_pressed(KeyCode::Z) {
+        editor_level.0.polylines.pop();
+    }
+    if keyboard.just_pressed(KeyCode::X) {
+        editor_level.0.stars.pop();
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (q_windows.get_single(), q_camera.get_single()) else { return; };
+    let Some(vec_mouse) = window.cursor_position()
+        .and_then(|position| cursor_to_world(camera, camera_transform, position)) else { return; };
+    if buttons.just_pressed(MouseButton::Right) {
+        editor_level.0.stars.push(vec_mouse);
+    }
+    if buttons.just_pressed(MouseButton::Middle) {
+        editor_level.0.player = vec_mouse;
+    }
+    if buttons.just_pressed(MouseButton::Left) {
+        editor_stroke.0 = Some(vec![vec_mouse]);
+    } else if buttons.pressed(MouseButton::Left) {
+        if let Some(stroke) = &mut editor_stroke.0 {
+            if stroke.last().is_none_or(|v| v.distance(vec_mouse) >= PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD) {
+                stroke.push(vec_mouse);
+            }
+        }
+    } else if buttons.just_released(MouseButton::Left) {
+        if let Some(stroke) = editor_stroke.0.take() {
+            if stroke.len() > 1 {
+                editor_level.0.polylines.push(stroke);
+            }
+        }
+    }
+}
+
+fn spawn_editor_polyline(commands: &mut Commands, vertices: &[Vec2], offset: Vec2, color: Color) {
+    if vertices.is_empty() { return; }
+    commands.spawn((
+        ShapeBundle {
+            path: polyline_path(vertices),
+            transform: Transform::from_xyz(offset.x, offset.y, SCENE_Z_INDEX),
+            ..default()
+        },
+        Stroke {
+            color,
+            options: StrokeOptions::default().with_line_width(LEVEL_SCENE_LINE_WIDTH).with_line_join(LineJoin::Round),
+        },
+        EditorEntity,
+    ));
+}
+
+pub fn draw_editor_level(
+    editor_level: Res<EditorLevel>,
+    q_editor: Query<Entity, (With<EditorEntity>, Without<Node>)>,
+    mut commands: Commands,
+) {
+    if !editor_level.is_changed() {
+        return;
+    }
+    for entity in q_editor.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let level = &editor_level.0;
+    // everything Ctrl+S writes back is shown, even what can't be edited here yet
+    for vertices in &level.polylines {
+        spawn_editor_polyline(&mut commands, vertices, Vec2::ZERO, Color::BLACK);
+    }
+    for vertices in &level.hazards {
+        spawn_editor_polyline(&mut commands, vertices, Vec2::ZERO, HAZARD_COLOR);
+    }
+    for vertices in &level.bouncy {
+        spawn_editor_polyline(&mut commands, vertices, Vec2::ZERO, BOUNCY_COLOR);
+    }
+    for platform in &level.platforms {
+        // where it starts, and faded where it turns back
+        spawn_editor_polyline(&mut commands, &platform.vertices, Vec2::ZERO, Color::BLACK);
+        spawn_editor_polyline(&mut commands, &platform.vertices, platform.offset, Color::GRAY);
+    }
+    for goal in &level.goals {
+        commands.spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Circle {
+                    radius: GOAL_RADIUS,
+                    center: Vec2::ZERO,
+                }),
+                transform: Transform::from_xyz(goal.x, goal.y, SCENE_Z_INDEX),
+                ..default()
+            },
+            Stroke::new(Color::BLACK, LEVEL_SCENE_LINE_WIDTH),
+            EditorEntity,
+        ));
+    }
+    for hint in &level.hints {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(hint.text.clone(), TextStyle {
+                    font_size: HINT_FONT_SIZE,
+                    color: Color::BLACK,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(hint.position.x, hint.position.y, SCENE_Z_INDEX),
+                ..default()
+            },
+            EditorEntity,
+        ));
+    }
+    for star in &level.stars {
+        commands.spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Circle {
+                    radius: STAR_RADIUS,
+                    center: Vec2::ZERO,
+                }),
+                transform: Transform::from_xyz(star.x, star.y, SCENE_Z_INDEX),
+                ..default()
+            },
+            Fill::color(Color::WHITE),
+            EditorEntity,
+        ));
+    }
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: PLAYER_RADIUS,
+                center: Vec2::ZERO,
+            }),
+            transform: Transform::from_xyz(level.player.x, level.player.y, SCENE_Z_INDEX),
+            ..default()
+        },
+        Stroke::new(Color::WHITE, LEVEL_SCENE_LINE_WIDTH),
+        EditorEntity,
+    ));
+}
+
+pub fn draw_editor_stroke(
+    editor_stroke: Res<EditorStroke>,
+    q_stroke: Query<Entity, With<EditorStrokeEntity>>,
+    mut commands: Commands,
+) {
+    if !editor_stroke.is_changed() {
+        return;
+    }
+    for entity in q_stroke.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(vertices) = &editor_stroke.0 else { return; };
+    commands.spawn((
+        ShapeBundle {
+            path: polyline_path(vertices),
+            transform: Transform::from_xyz(0., 0., SCENE_Z_INDEX),
+            ..default()
+        },
+        Stroke {
+            color: Color::GRAY,
+            options: StrokeOptions::default().with_line_width(LEVEL_SCENE_LINE_WIDTH).with_line_join(LineJoin::Round),
+        },
+        EditorStrokeEntity,
+    ));
+}
+
+pub fn save_edited_level(
+    keyboard: Res<Input<KeyCode>>,
+    editor_level: Res<EditorLevel>,
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+) {
+    if !(keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) && keyboard.just_pressed(KeyCode::S)) {
+        return;
+    }
+    let Some(entry) = all_levels.entry(level_state.id) else { return; };
+    let path = format!("assets/{}/{}", LEVELS_FOLDER, entry.file);
+    let text = match editor_level.0.write(LevelFormat::from_path(std::path::Path::new(&path))) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("Failed to serialize level {}: {}", path, e);
+            return;
+        }
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::write(&path, text) {
+        Ok(_) => info!("Saved level to {}", path),
+        Err(e) => error!("Failed to save level to {}: {}", path, e),
+    }
+    // no filesystem on the web, dump it to the console instead
+    #[cfg(target_arch = "wasm32")]
+    info!("Level {}:\n{}", path, text);
+}
+
+pub fn play_test_level(
+    keyboard: Res<Input<KeyCode>>,
+    editor_level: Res<EditorLevel>,
+    mut all_levels: ResMut<GameLevels>,
+    mut level_state: ResMut<LevelState>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard.just_pressed(EDITOR_TOGGLE_KEY) {
+        return;
+    }
+    let Some(slot) = all_levels.levels.get_mut(level_state.id) else { return; };
+    info!("Play-testing edited level {}", level_state.id);
+    *slot = Some(editor_level.0.clone());
+    level_state.stars = editor_level.0.stars.len();
+    next_state.set(GameState::Loading);
+}
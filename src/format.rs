@@ -0,0 +1,470 @@
+use std::{fmt::{self, Write}, path::Path};
+
+use bevy::prelude::*;
+
+use crate::level::{Level, Hint, MovingPlatform, WorldBounds};
+
+/// Ways a [`Level`] can be stored on disk, picked from the file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LevelFormat {
+    /// `.txt`, the `l/s/p` record format
+    Text,
+    /// `.level.json`
+    Json,
+    /// `.level.ron`
+    Ron,
+}
+
+impl LevelFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_lowercase();
+        if name.ends_with(".json") {
+            LevelFormat::Json
+        } else if name.ends_with(".ron") {
+            LevelFormat::Ron
+        } else {
+            LevelFormat::Text
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LevelFormat::Text => "txt",
+            LevelFormat::Json => "level.json",
+            LevelFormat::Ron => "level.ron",
+        }
+    }
+}
+
+/// Latest version of the text level format, written by [`Level::to_text`].
+///
+/// Files without a `v ` header are version 1 and may only contain `l `, `s ` and `p ` records.
+pub static LEVEL_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    Version,
+    Line,
+    Star,
+    Player,
+    Hazard,
+    Bouncy,
+    Platform,
+    Goal,
+    Hint,
+    Ink,
+    Bounds,
+}
+
+impl RecordKind {
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "v " => Some(RecordKind::Version),
+            "l " => Some(RecordKind::Line),
+            "s " => Some(RecordKind::Star),
+            "p " => Some(RecordKind::Player),
+            "h " => Some(RecordKind::Hazard),
+            "b " => Some(RecordKind::Bouncy),
+            "m " => Some(RecordKind::Platform),
+            "g " => Some(RecordKind::Goal),
+            "t " => Some(RecordKind::Hint),
+            "i " => Some(RecordKind::Ink),
+            "w " => Some(RecordKind::Bounds),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            RecordKind::Version => "v ",
+            RecordKind::Line => "l ",
+            RecordKind::Star => "s ",
+            RecordKind::Player => "p ",
+            RecordKind::Hazard => "h ",
+            RecordKind::Bouncy => "b ",
+            RecordKind::Platform => "m ",
+            RecordKind::Goal => "g ",
+            RecordKind::Hint => "t ",
+            RecordKind::Ink => "i ",
+            RecordKind::Bounds => "w ",
+        }
+    }
+
+    /// first format version the record appeared in
+    pub fn since_version(&self) -> u32 {
+        match self {
+            RecordKind::Line | RecordKind::Star | RecordKind::Player => 1,
+            RecordKind::Bounds => 3,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelParseErrorKind {
+    UnknownRecord,
+    MissingCoordinate,
+    MissingValue,
+    InvalidNumber,
+    TrailingData,
+    UnsupportedVersion,
+    MisplacedVersion,
+}
+
+/// Error returned by [`Level::parse`], positions are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub record: Option<RecordKind>,
+    pub kind: LevelParseErrorKind,
+}
+
+impl fmt::Display for LevelParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            LevelParseErrorKind::UnknownRecord => "unknown record",
+            LevelParseErrorKind::MissingCoordinate => "missing coordinate",
+            LevelParseErrorKind::MissingValue => "missing value",
+            LevelParseErrorKind::InvalidNumber => "invalid number",
+            LevelParseErrorKind::TrailingData => "unexpected data",
+            LevelParseErrorKind::UnsupportedVersion => "unsupported version",
+            LevelParseErrorKind::MisplacedVersion => "version header must be the first record",
+        };
+        write!(f, "line {}, column {}: {} `{}`", self.line, self.column, reason, self.token)?;
+        if let Some(record) = self.record {
+            write!(f, " in `{}` record", record.prefix().trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LevelParseError {}
+
+// tokens of a single record, remembers where it is for error reporting
+struct Record<'a> {
+    line: usize,
+    end_column: usize,
+    kind: RecordKind,
+    rest: &'a str,
+    column: usize,
+}
+
+impl<'a> Record<'a> {
+    fn error(&self, column: usize, token: &str, kind: LevelParseErrorKind) -> LevelParseError {
+        LevelParseError {
+            line: self.line,
+            column,
+            token: token.to_string(),
+            record: Some(self.kind),
+            kind,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<(usize, &'a str)> {
+        let trimmed = self.rest.trim_start_matches(' ');
+        self.column += self.rest.len() - trimmed.len();
+        if trimmed.is_empty() {
+            self.rest = trimmed;
+            return None;
+        }
+        let end = trimmed.find(' ').unwrap_or(trimmed.len());
+        let (token, rest) = trimmed.split_at(end);
+        let column = self.column;
+        self.column += token.len();
+        self.rest = rest;
+        Some((column, token))
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, LevelParseError> {
+        let (column, token) = self.next_token()
+            .ok_or_else(|| self.error(self.end_column, "", LevelParseErrorKind::MissingCoordinate))?;
+        let (x, y) = token.split_once(',')
+            .ok_or_else(|| self.error(column, token, LevelParseErrorKind::MissingCoordinate))?;
+        let x = x.parse::<f32>()
+            .map_err(|_| self.error(column, x, LevelParseErrorKind::InvalidNumber))?;
+        let y_column = column + token.len() - y.len();
+        let y = y.parse::<f32>()
+            .map_err(|_| self.error(y_column, y, LevelParseErrorKind::InvalidNumber))?;
+        Ok(Vec2::new(x, y))
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, LevelParseError> {
+        let (column, token) = self.next_token()
+            .ok_or_else(|| self.error(self.end_column, "", LevelParseErrorKind::MissingValue))?;
+        token.parse::<T>().map_err(|_| self.error(column, token, LevelParseErrorKind::InvalidNumber))
+    }
+
+    fn vertices(&mut self) -> Result<Vec<Vec2>, LevelParseError> {
+        let mut vertices = Vec::new();
+        while !self.rest.trim_start_matches(' ').is_empty() {
+            vertices.push(self.vec2()?);
+        }
+        Ok(vertices)
+    }
+
+    fn text(&mut self) -> Result<String, LevelParseError> {
+        let text = self.rest.trim();
+        if text.is_empty() {
+            return Err(self.error(self.end_column, "", LevelParseErrorKind::MissingValue));
+        }
+        self.rest = "";
+        Ok(text.to_string())
+    }
+
+    fn finish(&mut self) -> Result<(), LevelParseError> {
+        match self.next_token() {
+            Some((column, token)) => Err(self.error(column, token, LevelParseErrorKind::TrailingData)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Level {
+    pub fn parse(source: &str) -> Result<Self, LevelParseError> {
+        let mut level = Level::default();
+        let mut version = 1;
+        let mut first_record = true;
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') { continue; }
+            let prefix = line.get(..2).unwrap_or(line);
+            let kind = RecordKind::from_prefix(prefix)
+                .filter(|kind| *kind == RecordKind::Version || kind.since_version() <= version)
+                .ok_or_else(|| LevelParseError {
+                    line: line_number,
+                    column: 1,
+                    token: prefix.trim_end().to_string(),
+                    record: None,
+                    kind: LevelParseErrorKind::UnknownRecord,
+                })?;
+            let mut record = Record {
+                line: line_number,
+                end_column: line.len() + 1,
+                kind,
+                rest: &line[2..],
+                column: 3,
+            };
+            match kind {
+                RecordKind::Version => {
+                    if !first_record {
+                        return Err(record.error(1, prefix.trim_end(), LevelParseErrorKind::MisplacedVersion));
+                    }
+                    let column = record.column + record.rest.len() - record.rest.trim_start_matches(' ').len();
+                    version = record.number::<u32>()?;
+                    if version == 0 || version > LEVEL_FORMAT_VERSION {
+                        return Err(record.error(column, &version.to_string(), LevelParseErrorKind::UnsupportedVersion));
+                    }
+                }
+                RecordKind::Line => level.polylines.push(record.vertices()?),
+                RecordKind::Star => level.stars.extend(record.vertices()?),
+                RecordKind::Player => level.player = record.vec2()?,
+                RecordKind::Hazard => level.hazards.push(record.vertices()?),
+                RecordKind::Bouncy => level.bouncy.push(record.vertices()?),
+                RecordKind::Platform => {
+                    let offset = record.vec2()?;
+                    let period = record.number::<f32>()?;
+                    let vertices = record.vertices()?;
+                    level.platforms.push(MovingPlatform { vertices, offset, period });
+                }
+                RecordKind::Goal => level.goals.extend(record.vertices()?),
+                RecordKind::Hint => {
+                    let position = record.vec2()?;
+                    let text = record.text()?;
+                    level.hints.push(Hint { position, text });
+                }
+                RecordKind::Ink => level.ink = Some(record.number::<f32>()?),
+                RecordKind::Bounds => {
+                    let (a, b) = (record.vec2()?, record.vec2()?);
+                    level.bounds = Some(WorldBounds { min: a.min(b), max: a.max(b) });
+                }
+            }
+            record.finish()?;
+            first_record = false;
+        }
+        Ok(level)
+    }
+
+    /// Writes the level in the latest text format, [`Level::parse`] reads it back unchanged except for
+    /// hint texts, which are read back trimmed and on one line.
+    pub fn write_text(&self, w: &mut impl Write) -> fmt::Result {
+        writeln!(w, "{}{}", RecordKind::Version.prefix(), LEVEL_FORMAT_VERSION)?;
+        writeln!(w, "{}{}", RecordKind::Player.prefix(), vec2(self.player))?;
+        if let Some(ink) = self.ink {
+            writeln!(w, "{}{}", RecordKind::Ink.prefix(), ink)?;
+        }
+        if let Some(bounds) = self.bounds {
+            writeln!(w, "{}{} {}", RecordKind::Bounds.prefix(), vec2(bounds.min), vec2(bounds.max))?;
+        }
+        for polyline in &self.polylines {
+            write_vertices(w, RecordKind::Line, polyline)?;
+        }
+        if !self.stars.is_empty() {
+            write_vertices(w, RecordKind::Star, &self.stars)?;
+        }
+        for polyline in &self.hazards {
+            write_vertices(w, RecordKind::Hazard, polyline)?;
+        }
+        for polyline in &self.bouncy {
+            write_vertices(w, RecordKind::Bouncy, polyline)?;
+        }
+        for platform in &self.platforms {
+            write!(w, "{}{} {}", RecordKind::Platform.prefix(), vec2(platform.offset), platform.period)?;
+            for v in &platform.vertices {
+                write!(w, " {}", vec2(*v))?;
+            }
+            writeln!(w)?;
+        }
+        if !self.goals.is_empty() {
+            write_vertices(w, RecordKind::Goal, &self.goals)?;
+        }
+        for hint in &self.hints {
+            writeln!(w, "{}{} {}", RecordKind::Hint.prefix(), vec2(hint.position), hint.text.replace('\n', " "))?;
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        self.write_text(&mut text).expect("writing to a String can't fail");
+        text
+    }
+}
+
+fn vec2(v: Vec2) -> String {
+    format!("{},{}", v.x, v.y)
+}
+
+fn write_vertices(w: &mut impl Write, kind: RecordKind, vertices: &[Vec2]) -> fmt::Result {
+    w.write_str(kind.prefix())?;
+    let vertices = vertices.iter().map(|v| vec2(*v)).collect::<Vec<_>>();
+    writeln!(w, "{}", vertices.join(" "))
+}
+
+impl Level {
+    pub fn from_json(source: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(source)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_ron(source: &str) -> ron::error::SpannedResult<Self> {
+        ron::from_str(source)
+    }
+
+    pub fn to_ron(&self) -> ron::Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Reads a level stored in `format`.
+    pub fn read(source: &str, format: LevelFormat) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match format {
+            LevelFormat::Text => Level::parse(source)?,
+            LevelFormat::Json => Level::from_json(source)?,
+            LevelFormat::Ron => Level::from_ron(source)?,
+        })
+    }
+
+    pub fn write(&self, format: LevelFormat) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match format {
+            LevelFormat::Text => self.to_text(),
+            LevelFormat::Json => self.to_json()?,
+            LevelFormat::Ron => self.to_ron()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one of every record, in the order `write_text` writes them
+    const EVERY_RECORD: &str = "\
+v 3
+p 0,100
+i 500
+w -400,-300 400,300
+l -200,0 200,0
+s 0,-100 50,-100
+h -100,-200 100,-200
+b 150,50 250,50
+m 0,50 2 -50,0 50,0
+g 300,-250
+t 0,150 draw a line under the ball
+";
+
+    #[test]
+    fn bad_number_is_reported_where_it_is() {
+        let error = Level::parse("v 3\np 0,abc\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (2, 5, "abc"));
+        assert_eq!(error.record, Some(RecordKind::Player));
+        assert_eq!(error.kind, LevelParseErrorKind::InvalidNumber);
+    }
+
+    #[test]
+    fn missing_y_is_reported() {
+        let error = Level::parse("l 0,0 10\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (1, 7, "10"));
+        assert_eq!(error.record, Some(RecordKind::Line));
+        assert_eq!(error.kind, LevelParseErrorKind::MissingCoordinate);
+    }
+
+    #[test]
+    fn trailing_data_after_player_is_reported() {
+        let error = Level::parse("p 0,100  5,5\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (1, 10, "5,5"));
+        assert_eq!(error.record, Some(RecordKind::Player));
+        assert_eq!(error.kind, LevelParseErrorKind::TrailingData);
+    }
+
+    #[test]
+    fn unknown_prefix_is_reported_on_its_line() {
+        // comments and blank lines still count
+        let error = Level::parse("# level\n\nq 1,2\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (3, 1, "q"));
+        assert_eq!(error.record, None);
+        assert_eq!(error.kind, LevelParseErrorKind::UnknownRecord);
+    }
+
+    #[test]
+    fn every_record_round_trips() {
+        let level = Level::parse(EVERY_RECORD).unwrap();
+        assert_eq!(level.to_text(), EVERY_RECORD);
+        assert_eq!(Level::parse(&level.to_text()).unwrap(), level);
+    }
+
+    #[test]
+    fn hint_text_is_written_on_one_line() {
+        let mut level = Level::default();
+        level.hints.push(Hint { position: Vec2::ZERO, text: "two\nlines".to_string() });
+        let level = Level::parse(&level.to_text()).unwrap();
+        assert_eq!(level.hints[0].text, "two lines");
+    }
+
+    #[test]
+    fn newer_record_in_version_1_is_rejected() {
+        let error = Level::parse("p 0,100\nh -100,-200 100,-200\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (2, 1, "h"));
+        assert_eq!(error.kind, LevelParseErrorKind::UnknownRecord);
+    }
+
+    #[test]
+    fn unknown_record_is_rejected() {
+        let error = Level::parse("v 3\nz 1,2\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (2, 1, "z"));
+        assert_eq!(error.record, None);
+        assert_eq!(error.kind, LevelParseErrorKind::UnknownRecord);
+    }
+
+    #[test]
+    fn version_after_other_records_is_rejected() {
+        let error = Level::parse("p 0,100\nv 2\n").unwrap_err();
+        assert_eq!((error.line, error.column, error.token.as_str()), (2, 1, "v"));
+        assert_eq!(error.record, Some(RecordKind::Version));
+        assert_eq!(error.kind, LevelParseErrorKind::MisplacedVersion);
+    }
+}
@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameLevels, LevelState, Lines, PlayerStatus, Progress, Attempt, Ink, polyline_path,
+    PLAYER_RADIUS, PLAYER_DRAW_LINE_WIDTH,
+};
+
+pub static GHOST_KEY: KeyCode = KeyCode::G;
+pub static GHOST_BALL_COLOR: Color = Color::rgba(1., 1., 1., 0.35);
+pub static GHOST_STROKE_COLOR: Color = Color::rgba(0.5, 0.5, 0.5, 0.25);
+// under the live strokes and ball
+pub static GHOST_Z_INDEX: f32 = 0.5;
+
+/// A finished run of a level: its strokes, and where the ball was how many physics steps after
+/// it was released.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GhostRun {
+    pub strokes: u32,
+    pub ink: f32,
+    pub lines: Vec<Vec<Vec2>>,
+    pub trajectory: Vec<(u32, Vec2)>,
+}
+
+impl GhostRun {
+    /// Fewer strokes first, then less ink, like the records in [`Progress`].
+    pub fn is_better_than(&self, other: &GhostRun) -> bool {
+        (self.strokes, self.ink) < (other.strokes, other.ink)
+    }
+
+    /// Where the ball was `step` physics steps after release, between the recorded positions.
+    pub fn position_at(&self, step: u32) -> Option<Vec2> {
+        let next = self.trajectory.partition_point(|(s, _)| *s < step);
+        match (next.checked_sub(1).map(|i| self.trajectory[i]), self.trajectory.get(next).copied()) {
+            (Some((s0, p0)), Some((s1, p1))) => Some(p0.lerp(p1, (step - s0) as f32 / (s1 - s0) as f32)),
+            (None, Some((_, p))) | (Some((_, p)), None) => Some(p),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Whether the ghost of the best run is shown, toggled with [`GHOST_KEY`].
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct GhostConfig {
+    pub visible: bool,
+}
+
+impl Default for GhostConfig {
+    fn default() -> Self {
+        GhostConfig { visible: true }
+    }
+}
+
+/// The live ball's trajectory, and the physics steps since it was released, which the ghost follows.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct GhostRecorder {
+    pub since_release: Option<u32>,
+    pub trajectory: Vec<(u32, Vec2)>,
+}
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct GhostEntity;
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct GhostBall;
+
+pub fn spawn_ghost(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    progress: Res<Progress>,
+    config: Res<GhostConfig>,
+    mut recorder: ResMut<GhostRecorder>,
+    mut commands: Commands,
+) {
+    *recorder = GhostRecorder::default();
+    let Some(ghost) = progress.ghost(&all_levels, level_state.id) else { return; };
+    let Some(start) = ghost.position_at(0) else { return; };
+    let visibility = if config.visible { Visibility::Inherited } else { Visibility::Hidden };
+    for vertices in &ghost.lines {
+        commands.spawn((
+            ShapeBundle {
+                path: polyline_path(vertices),
+                transform: Transform::from_xyz(0., 0., GHOST_Z_INDEX),
+                visibility,
+                ..default()
+            },
+            Stroke {
+                color: GHOST_STROKE_COLOR,
+                options: StrokeOptions::default()
+                    .with_line_width(PLAYER_DRAW_LINE_WIDTH)
+                    .with_line_join(LineJoin::Round)
+                    .with_line_cap(LineCap::Round),
+            },
+            GhostEntity,
+        ));
+    }
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: PLAYER_RADIUS,
+                center: Vec2::ZERO,
+            }),
+            transform: Transform::from_translation(start.extend(GHOST_Z_INDEX)),
+            visibility,
+            ..default()
+        },
+        Fill::color(GHOST_BALL_COLOR),
+        GhostEntity,
+        GhostBall,
+    ));
+}
+
+pub fn despawn_ghost(
+    q_ghost: Query<Entity, With<GhostEntity>>,
+    mut commands: Commands,
+) {
+    for entity in q_ghost.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Records where the live ball is before each step from the one it's released in, a restart or
+/// retry starts over.
+pub fn record_ghost(
+    q_player: Query<(&Transform, &GravityScale), With<PlayerStatus>>,
+    mut recorder: ResMut<GhostRecorder>,
+) {
+    let Ok((transform, gravity)) = q_player.get_single() else { return; };
+    if gravity.0 == 0. {
+        *recorder = GhostRecorder::default();
+        return;
+    }
+    let step = recorder.since_release.map_or(0, |step| step + 1);
+    recorder.since_release = Some(step);
+    recorder.trajectory.push((step, transform.translation.truncate()));
+}
+
+/// Moves the ghost ball to where the best run was as many steps after its release.
+pub fn play_ghost(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    progress: Res<Progress>,
+    recorder: Res<GhostRecorder>,
+    mut q_ball: Query<&mut Transform, With<GhostBall>>,
+) {
+    let Some(ghost) = progress.ghost(&all_levels, level_state.id) else { return; };
+    let Some(position) = ghost.position_at(recorder.since_release.unwrap_or(0)) else { return; };
+    for mut transform in q_ball.iter_mut() {
+        transform.translation = position.extend(GHOST_Z_INDEX);
+    }
+}
+
+pub fn toggle_ghost(
+    keyboard: Res<Input<KeyCode>>,
+    mut config: ResMut<GhostConfig>,
+    mut q_ghost: Query<&mut Visibility, With<GhostEntity>>,
+) {
+    if !keyboard.just_pressed(GHOST_KEY) {
+        return;
+    }
+    config.visible = !config.visible;
+    info!("Ghost {}", if config.visible { "shown" } else { "hidden" });
+    for mut visibility in q_ghost.iter_mut() {
+        *visibility = if config.visible { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+/// Keeps the finished run as the level's ghost when it beats the one there.
+pub fn record_ghost_run(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    attempt: Res<Attempt>,
+    ink: Res<Ink>,
+    lines: Res<Lines>,
+    recorder: Res<GhostRecorder>,
+    mut progress: ResMut<Progress>,
+) {
+    if recorder.trajectory.is_empty() {
+        return;
+    }
+    let run = GhostRun {
+        strokes: attempt.strokes,
+        ink: ink.used,
+        lines: lines.0.values().cloned().collect(),
+        trajectory: recorder.trajectory.clone(),
+    };
+    if progress.ghost(&all_levels, level_state.id).is_none_or(|best| run.is_better_than(best)) {
+        info!("New best run of level {}", level_state.id);
+        progress.set_ghost(&all_levels, level_state.id, run);
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::{Lines, Ink, StrokeStyle, spawn_stroke, polyline_length};
+
+pub static HISTORY_MAX_LEN: usize = 256;
+
+/// One undoable change to the player's lines: the lines it removed, with their vertices,
+/// and the entities it added.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineEdit {
+    pub removed: Vec<(Entity, Vec<Vec2>)>,
+    pub added: Vec<Entity>,
+}
+
+impl LineEdit {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+#[derive(Debug, Resource, Default, Clone)]
+pub struct LineHistory {
+    undo: Vec<LineEdit>,
+    redo: Vec<LineEdit>,
+    // an erase gesture lasts as long as the button is held, it is undone in one go
+    erasing: Option<LineEdit>,
+}
+
+impl LineHistory {
+    pub fn record(&mut self, edit: LineEdit) {
+        self.finish_erase();
+        self.push(edit);
+    }
+
+    /// Records one line split by the eraser, merged into the current erase gesture.
+    pub fn record_erase(&mut self, removed: (Entity, Vec<Vec2>), added: Vec<Entity>) {
+        let edit = self.erasing.get_or_insert_with(LineEdit::default);
+        // a piece cut earlier in the same gesture never existed as far as undo is concerned
+        if let Some(i) = edit.added.iter().position(|e| *e == removed.0) {
+            edit.added.remove(i);
+        } else {
+            edit.removed.push(removed);
+        }
+        edit.added.extend(added);
+    }
+
+    pub fn finish_erase(&mut self) {
+        if let Some(edit) = self.erasing.take() {
+            if !edit.is_empty() {
+                self.push(edit);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.erasing = None;
+    }
+
+    fn push(&mut self, edit: LineEdit) {
+        self.redo.clear();
+        self.undo.push(edit);
+        if self.undo.len() > HISTORY_MAX_LEN {
+            self.undo.remove(0);
+        }
+    }
+
+    // respawned lines get new entities, older edits must follow them
+    fn remap(&mut self, old: Entity, new: Entity) {
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for e in edit.added.iter_mut().chain(edit.removed.iter_mut().map(|(e, _)| e)) {
+                if *e == old {
+                    *e = new;
+                }
+            }
+        }
+    }
+
+    /// Reverts `edit`, returning the edit that reverts it back.
+    fn apply_inverse(&mut self, edit: LineEdit, lines: &mut Lines, commands: &mut Commands) -> LineEdit {
+        let mut inverse = LineEdit::default();
+        for e in edit.added {
+            if let Some(vertices) = lines.0.remove(&e) {
+                if let Some(entity) = commands.get_entity(e) {
+                    entity.despawn_recursive();
+                }
+                inverse.removed.push((e, vertices));
+            }
+        }
+        for (old, vertices) in edit.removed {
+            if vertices.is_empty() { continue; }
+            let new = spawn_stroke(commands, &vertices, StrokeStyle::default());
+            lines.0.insert(new, vertices);
+            self.remap(old, new);
+            inverse.added.push(new);
+        }
+        inverse
+    }
+
+    pub fn undo(&mut self, lines: &mut Lines, commands: &mut Commands) -> bool {
+        self.finish_erase();
+        let Some(edit) = self.undo.pop() else { return false; };
+        let inverse = self.apply_inverse(edit, lines, commands);
+        self.redo.push(inverse);
+        true
+    }
+
+    pub fn redo(&mut self, lines: &mut Lines, commands: &mut Commands) -> bool {
+        self.finish_erase();
+        let Some(edit) = self.redo.pop() else { return false; };
+        let inverse = self.apply_inverse(edit, lines, commands);
+        self.undo.push(inverse);
+        true
+    }
+
+    /// Undoes the last edit, or redoes the last undone one, and charges or gives back the ink
+    /// of the lines it brings back or removes.
+    pub fn undo_redo(&mut self, redo: bool, lines: &mut Lines, ink: &mut Ink, commands: &mut Commands) {
+        let length_before = lines.0.values().map(|v| polyline_length(v)).sum::<f32>();
+        if redo {
+            if self.redo(lines, commands) {
+                info!("Redo line edit");
+            }
+        } else if self.undo(lines, commands) {
+            info!("Undo line edit");
+        }
+        // redo only ever brings back lines that were paid for before
+        let length_after = lines.0.values().map(|v| polyline_length(v)).sum::<f32>();
+        ink.used = (ink.used + length_after - length_before).max(0.);
+    }
+}
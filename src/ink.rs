@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use crate::{GameLevels, LevelState};
+
+pub static INK_HUD_FONT_SIZE: f32 = 24.;
+
+/// Length of lines the player can still draw in the current level.
+#[derive(Debug, Clone, Copy, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Ink {
+    pub budget: Option<f32>,
+    pub used: f32,
+}
+
+impl Ink {
+    pub fn remaining(&self) -> f32 {
+        self.budget.map_or(f32::INFINITY, |budget| (budget - self.used).max(0.))
+    }
+
+    /// Takes `amount` of ink, or nothing if there isn't enough left.
+    pub fn spend(&mut self, amount: f32) -> bool {
+        if amount > self.remaining() {
+            return false;
+        }
+        self.used += amount;
+        true
+    }
+
+    /// Gives back `amount` of ink, nothing when it's negative: smoothing that lengthens a line is free.
+    pub fn refund(&mut self, amount: f32) {
+        self.used = (self.used - amount.max(0.)).max(0.);
+    }
+}
+
+pub fn polyline_length(vertices: &[Vec2]) -> f32 {
+    vertices.windows(2).map(|w| w[0].distance(w[1])).sum()
+}
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct InkText;
+
+pub fn reset_ink(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut ink: ResMut<Ink>,
+) {
+    *ink = Ink {
+        budget: all_levels.get(level_state.id).and_then(|l| l.ink),
+        used: 0.,
+    };
+}
+
+pub fn setup_ink_hud(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: INK_HUD_FONT_SIZE,
+            color: Color::BLACK,
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            top: Val::Px(10.),
+            ..default()
+        }),
+        InkText,
+    ));
+}
+
+pub fn update_ink_hud(
+    ink: Res<Ink>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<InkText>>,
+) {
+    if !ink.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in q_text.iter_mut() {
+        let Some(budget) = ink.budget else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Visible;
+        let percent = if budget > 0. { ink.remaining() / budget * 100. } else { 0. };
+        text.sections[0].value = format!("Ink {:.0}%", percent);
+    }
+}
@@ -0,0 +1,250 @@
+use bevy::{prelude::*, window::PrimaryWindow, input::touch::Touch};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{MainCamera, PlayerStatus, PLAYER_RADIUS, cursor_to_world};
+
+pub static GAMEPAD_CURSOR_SPEED: f32 = 600.;
+pub static GAMEPAD_CURSOR_RADIUS: f32 = 8.;
+pub static GAMEPAD_CURSOR_Z_INDEX: f32 = 10.;
+
+/// Drawing actions in world coordinates, whatever device they came from.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub enum DrawInput {
+    Begin(Vec2),
+    Extend(Vec2),
+    End,
+    EraseAt(Vec2),
+    EraseEnd,
+    Undo,
+    Redo,
+}
+
+/// Touches currently drawing and erasing: the first finger down draws, a second one erases.
+/// Styluses and pens are reported as touches, so they draw the same way.
+#[derive(Debug, Resource, Default, Clone, Copy)]
+pub struct DrawTouches {
+    pub draw: Option<u64>,
+    pub erase: Option<u64>,
+}
+
+/// Virtual cursor moved with the left stick of the first gamepad, South draws and East erases.
+#[derive(Debug, Resource, Default, Clone, Copy)]
+pub struct GamepadCursor {
+    pub gamepad: Option<Gamepad>,
+    pub position: Vec2,
+}
+
+/// Lets the ball fall, whatever device asked for it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Event)]
+pub struct ReleaseGravity;
+
+/// Puts the ball and stars back where the level starts them, see [`restart_level`](crate::restart_level).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Event)]
+pub struct RestartLevel {
+    pub clear_lines: bool,
+}
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct GamepadCursorEntity;
+
+/// Drops the inputs no step handled before the last level ended, they're only read while playing.
+pub fn clear_step_inputs(
+    mut draw_input: ResMut<Events<DrawInput>>,
+    mut release: ResMut<Events<ReleaseGravity>>,
+    mut restart: ResMut<Events<RestartLevel>>,
+) {
+    draw_input.clear();
+    release.clear();
+    restart.clear();
+}
+
+pub static RESTART_KEY: KeyCode = KeyCode::R;
+
+pub fn read_gravity_input(
+    keyboard: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    touches: Res<Touches>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    q_player: Query<&Transform, With<PlayerStatus>>,
+    mut release: EventWriter<ReleaseGravity>,
+) {
+    // no keyboard on phones, tapping the ball lets it go
+    let tapped = match (q_camera.get_single(), q_player.get_single()) {
+        (Ok((camera, camera_transform)), Ok(transform)) => touches.iter_just_pressed()
+            .filter_map(|touch| cursor_to_world(camera, camera_transform, touch.position()))
+            .any(|position| position.distance(transform.translation.truncate()) < PLAYER_RADIUS),
+        _ => false,
+    };
+    let gamepad = gamepad_buttons.get_just_pressed().any(|button| button.button_type == GamepadButtonType::North);
+    if keyboard.just_pressed(KeyCode::Space) || tapped || gamepad {
+        release.send(ReleaseGravity);
+    }
+}
+
+/// `R` or the gamepad's Select restarts, holding `Shift` also clears the lines.
+pub fn read_restart_input(
+    keyboard: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut restart: EventWriter<RestartLevel>,
+) {
+    let gamepad = gamepad_buttons.get_just_pressed().any(|button| button.button_type == GamepadButtonType::Select);
+    if keyboard.just_pressed(RESTART_KEY) || gamepad {
+        restart.send(RestartLevel {
+            clear_lines: keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+        });
+    }
+}
+
+/// `Ctrl+Z` undoes the last line edit, `Ctrl+Shift+Z` redoes it.
+pub fn read_undo_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut draw_input: EventWriter<DrawInput>,
+) {
+    if !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) || !keyboard.just_pressed(KeyCode::Z) {
+        return;
+    }
+    if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        draw_input.send(DrawInput::Redo);
+    } else {
+        draw_input.send(DrawInput::Undo);
+    }
+}
+
+pub fn read_mouse_input(
+    buttons: Res<Input<MouseButton>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut draw_input: EventWriter<DrawInput>,
+) {
+    // releases are sent even with the cursor outside the window
+    if buttons.just_released(MouseButton::Left) {
+        draw_input.send(DrawInput::End);
+    }
+    if buttons.just_released(MouseButton::Right) {
+        draw_input.send(DrawInput::EraseEnd);
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (q_windows.get_single(), q_camera.get_single()) else { return; };
+    let Some(position) = window.cursor_position()
+        .and_then(|position| cursor_to_world(camera, camera_transform, position)) else { return; };
+    if buttons.just_pressed(MouseButton::Left) {
+        draw_input.send(DrawInput::Begin(position));
+    } else if buttons.pressed(MouseButton::Left) {
+        draw_input.send(DrawInput::Extend(position));
+    }
+    if buttons.pressed(MouseButton::Right) {
+        draw_input.send(DrawInput::EraseAt(position));
+    }
+}
+
+pub fn read_touch_input(
+    touches: Res<Touches>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut draw_touches: ResMut<DrawTouches>,
+    mut draw_input: EventWriter<DrawInput>,
+) {
+    for touch in touches.iter_just_released().chain(touches.iter_just_canceled()) {
+        if draw_touches.draw == Some(touch.id()) {
+            draw_touches.draw = None;
+            draw_input.send(DrawInput::End);
+        }
+        if draw_touches.erase == Some(touch.id()) {
+            draw_touches.erase = None;
+            draw_input.send(DrawInput::EraseEnd);
+        }
+    }
+    let Ok((camera, camera_transform)) = q_camera.get_single() else { return; };
+    let world = |touch: &Touch| cursor_to_world(camera, camera_transform, touch.position());
+    for touch in touches.iter_just_pressed() {
+        let Some(position) = world(touch) else { continue; };
+        if draw_touches.draw.is_none() {
+            draw_touches.draw = Some(touch.id());
+            draw_input.send(DrawInput::Begin(position));
+        } else if draw_touches.erase.is_none() {
+            draw_touches.erase = Some(touch.id());
+        }
+    }
+    for touch in touches.iter() {
+        let Some(position) = world(touch) else { continue; };
+        if draw_touches.draw == Some(touch.id()) && !touches.just_pressed(touch.id()) {
+            draw_input.send(DrawInput::Extend(position));
+        }
+        if draw_touches.erase == Some(touch.id()) {
+            draw_input.send(DrawInput::EraseAt(position));
+        }
+    }
+}
+
+pub fn read_gamepad_input(
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut cursor: ResMut<GamepadCursor>,
+    mut draw_input: EventWriter<DrawInput>,
+) {
+    let gamepad = gamepads.iter().next();
+    if cursor.gamepad != gamepad {
+        // a new gamepad starts in the middle of the screen
+        let center = q_camera.get_single().map_or(Vec2::ZERO, |(_, transform)| transform.translation().truncate());
+        *cursor = GamepadCursor { gamepad, position: center };
+    }
+    let Some(gamepad) = gamepad else { return; };
+    let stick = Vec2::new(
+        axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.),
+        axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.),
+    );
+    cursor.position += stick * GAMEPAD_CURSOR_SPEED * time.delta_seconds();
+    // keep the cursor on screen, wherever the camera looks
+    if let Ok((camera, camera_transform)) = q_camera.get_single() {
+        let corners = camera.logical_viewport_size().and_then(|size| Some((
+            camera.viewport_to_world_2d(camera_transform, Vec2::ZERO)?,
+            camera.viewport_to_world_2d(camera_transform, size)?,
+        )));
+        if let Some((a, b)) = corners {
+            cursor.position = cursor.position.clamp(a.min(b), a.max(b));
+        }
+    }
+
+    let draw = GamepadButton::new(gamepad, GamepadButtonType::South);
+    let erase = GamepadButton::new(gamepad, GamepadButtonType::East);
+    if buttons.just_pressed(draw) {
+        draw_input.send(DrawInput::Begin(cursor.position));
+    } else if buttons.pressed(draw) {
+        draw_input.send(DrawInput::Extend(cursor.position));
+    } else if buttons.just_released(draw) {
+        draw_input.send(DrawInput::End);
+    }
+    if buttons.pressed(erase) {
+        draw_input.send(DrawInput::EraseAt(cursor.position));
+    } else if buttons.just_released(erase) {
+        draw_input.send(DrawInput::EraseEnd);
+    }
+}
+
+pub fn setup_gamepad_cursor(mut commands: Commands) {
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: GAMEPAD_CURSOR_RADIUS,
+                center: Vec2::ZERO,
+            }),
+            transform: Transform::from_xyz(0., 0., GAMEPAD_CURSOR_Z_INDEX),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Stroke::new(Color::BLACK, 2.),
+        GamepadCursorEntity,
+    ));
+}
+
+pub fn update_gamepad_cursor(
+    cursor: Res<GamepadCursor>,
+    mut q_cursor: Query<(&mut Transform, &mut Visibility), With<GamepadCursorEntity>>,
+) {
+    for (mut transform, mut visibility) in q_cursor.iter_mut() {
+        *visibility = if cursor.gamepad.is_some() { Visibility::Visible } else { Visibility::Hidden };
+        transform.translation.x = cursor.position.x;
+        transform.translation.y = cursor.position.y;
+    }
+}
@@ -1,134 +1,328 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, LoadState}, ecs::system::{EntityCommands, SystemParam}, reflect::{TypePath, TypeUuid}, utils::{BoxedFuture, Duration}};
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_kira_audio::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub static SCENE_Z_INDEX: f32 = -1.;
 pub static STAR_Z_INDEX: f32 = 3.;
 pub static LEVEL_SCENE_LINE_WIDTH: f32 = 5.;
 pub static STAR_RADIUS: f32 = 15.;
 pub static SLOW_CLEAN_LEVEL_ALPHA_STEP: f32 = 0.04;
+pub static GOAL_RADIUS: f32 = 30.;
+pub static HINT_FONT_SIZE: f32 = 24.;
+pub static BOUNCY_RESTITUTION: f32 = 1.2;
+pub static HAZARD_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
+pub static BOUNCY_COLOR: Color = Color::rgb(0.2, 0.5, 0.9);
+pub static LEVELS_FOLDER: &str = "levels";
+pub static LEVEL_MUSIC_VOLUME: f64 = 0.62;
 
-use crate::{GameState, ColliderType, PlayerStatus};
+use crate::{GameState, ColliderType, PlayerStatus, Lines, PlayerCurrentLineEntity, LineHistory, LevelManifest, LevelEntry, LevelFormat, LEVEL_MANIFEST_FILE, PHYSICS_TIMESTEP, logical_size};
 
 #[derive(Debug, Clone, Copy, Default, Resource, Reflect)]
 #[reflect(Resource)]
 pub struct LevelState {
+    /// position of the level in the manifest
     pub id: usize,
     pub stars: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "7c1b3f0e-5d0a-4a8e-9f43-6b2f1f0d9a51"]
+#[serde(default)]
 pub struct Level {
     pub player: Vec2,
     pub polylines: Vec<Vec<Vec2>>,
     pub stars: Vec<Vec2>,
+    /// polylines that send the ball back to the start
+    pub hazards: Vec<Vec<Vec2>>,
+    pub bouncy: Vec<Vec<Vec2>>,
+    pub platforms: Vec<MovingPlatform>,
+    /// when present, the level ends by reaching a goal after collecting all stars
+    pub goals: Vec<Vec2>,
+    pub hints: Vec<Hint>,
+    /// total length the player can draw, unlimited when `None`
+    pub ink: Option<f32>,
+    /// area the camera can scroll over, the logical screen around the origin when `None`
+    pub bounds: Option<WorldBounds>,
 }
 
 impl Level {
-    pub fn load_all_levels() -> Vec<Self> {
-        let levels = [
-            include_str!("./levels/welcome.txt"),
-            include_str!("./levels/howareu.txt"),
-        ];
-
-        levels.iter().map(|f| {
-            let mut polylines = Vec::new();
-            let mut stars = Vec::new();
-            let mut player = Vec2::ZERO;
-            for line in f.lines() {
-                let mut vertices = Vec::new();
-                let (mode, data) = line.split_at(2);
-                match mode {
-                    "l " => {
-                        for vertex in data.split(' ') {
-                            let mut coords = vertex.split(',');
-                            let x = coords.next().unwrap().parse::<f32>().unwrap();
-                            let y = coords.next().unwrap().parse::<f32>().unwrap();
-                            vertices.push(Vec2::new(x, y));
-                        }
-                    }
-                    "s " => {
-                        for star in data.split(' ') {
-                            let mut coords = star.split(',');
-                            let x = coords.next().unwrap().parse::<f32>().unwrap();
-                            let y = coords.next().unwrap().parse::<f32>().unwrap();
-                            stars.push(Vec2::new(x, y));
-                        }
-                    }
-                    "p " => {
-                        let mut coords = data.split(',');
-                        let x = coords.next().unwrap().parse::<f32>().unwrap();
-                        let y = coords.next().unwrap().parse::<f32>().unwrap();
-                        player = Vec2::new(x, y);
-                    }
-                    _ => { error!("Not supported parsing mode {} ", mode); }
-                }
-                polylines.push(vertices);
-            }
-            Level {
-                player,
-                polylines,
-                stars,
-            }
-        }).collect()
+    pub fn world_bounds(&self) -> WorldBounds {
+        self.bounds.unwrap_or_else(|| WorldBounds {
+            min: -logical_size() / 2.,
+            max: logical_size() / 2.,
+        })
     }
 }
 
-#[derive(Debug, Resource, Clone)]
-pub struct GameLevels(pub Vec<Level>);
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
 
-impl Default for GameLevels {
-    fn default() -> Self {
-        GameLevels(Vec::new())
+impl WorldBounds {
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
     }
 }
 
+/// A polyline travelling back and forth by `offset`, once every `period` seconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MovingPlatform {
+    pub vertices: Vec<Vec2>,
+    pub offset: Vec2,
+    pub period: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Hint {
+    pub position: Vec2,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct PlatformMotion {
+    pub offset: Vec2,
+    pub period: f32,
+    pub elapsed: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes)?;
+            let level = match LevelFormat::from_path(load_context.path()) {
+                LevelFormat::Text => Level::parse(source)?,
+                LevelFormat::Json => Level::from_json(source)?,
+                LevelFormat::Ron => Level::from_ron(source)?,
+            };
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt", "level.json", "level.ron"]
+    }
+}
+
+/// Levels in manifest order, `None` for the ones that failed to load.
+#[derive(Debug, Resource, Clone, Default)]
+pub struct GameLevels {
+    pub manifest: LevelManifest,
+    pub levels: Vec<Option<Level>>,
+}
+
+impl GameLevels {
+    /// Levels that don't come from the manifest, named after their position.
+    pub fn from_levels(levels: Vec<Level>) -> Self {
+        GameLevels {
+            manifest: LevelManifest {
+                levels: (0..levels.len()).map(|i| LevelEntry {
+                    file: format!("level-{}.txt", i + 1),
+                    title: format!("Level {}", i + 1),
+                    ..default()
+                }).collect(),
+            },
+            levels: levels.into_iter().map(Some).collect(),
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Level> {
+        self.levels.get(id).and_then(|l| l.as_ref())
+    }
+
+    pub fn entry(&self, id: usize) -> Option<&LevelEntry> {
+        self.manifest.levels.get(id)
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.levels.iter().position(|l| l.is_some())
+    }
+
+    /// next playable level after `id` in manifest order
+    pub fn next(&self, id: usize) -> Option<usize> {
+        (id + 1..self.levels.len()).find(|i| self.levels[*i].is_some())
+    }
+}
+
+/// A level's hint text, it has no collider but loads and cleans with the rest of the scene.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct LevelHint;
+
+/// Everything a level spawns into the scene.
+pub type LevelScene = Or<(With<ColliderType>, With<LevelHint>)>;
+
+#[derive(Debug, Resource, Clone, Default)]
+pub struct LevelHandles {
+    pub manifest: Handle<LevelManifest>,
+    pub levels: Vec<Handle<Level>>,
+}
 
 pub fn load_all_levels(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(LevelHandles {
+        manifest: asset_server.load(LEVEL_MANIFEST_FILE),
+        levels: Vec::new(),
+    });
+}
+
+/// The manifest and level files being loaded, and what's loaded of them.
+#[derive(SystemParam)]
+pub struct LevelAssets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    manifests: Res<'w, Assets<LevelManifest>>,
+    levels: Res<'w, Assets<Level>>,
+}
+
+pub fn wait_for_levels(
+    assets: LevelAssets,
+    mut level_handles: ResMut<LevelHandles>,
     mut all_levels: ResMut<GameLevels>,
-    mut current_level_state: ResMut<LevelState>
+    mut current_level_state: ResMut<LevelState>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut reported: Local<bool>,
 ) {
-    all_levels.0 = Level::load_all_levels();
-    let level_brief = all_levels.0.iter().map(|level| {
-        format!("{} polylines, {} stars", level.polylines.len(), level.stars.len())
+    let LevelAssets { asset_server, manifests, levels } = assets;
+    let Some(manifest) = manifests.get(&level_handles.manifest) else {
+        // the asset server logged why, there's nothing to wait for
+        if asset_server.get_load_state(&level_handles.manifest) == LoadState::Failed && !*reported {
+            error!("Failed to load the level manifest {}, no levels to play", LEVEL_MANIFEST_FILE);
+            *reported = true;
+        }
+        return;
+    };
+    if level_handles.levels.len() != manifest.levels.len() {
+        level_handles.levels = manifest.levels.iter()
+            .map(|entry| asset_server.load(format!("{}/{}", LEVELS_FOLDER, entry.file)))
+            .collect();
+        return;
+    }
+    if level_handles.levels.iter().any(|h| matches!(asset_server.get_load_state(h), LoadState::NotLoaded | LoadState::Loading)) {
+        return;
+    }
+    // failed levels have already been logged by the asset server, they are skipped
+    all_levels.manifest = manifest.clone();
+    all_levels.levels = level_handles.levels.iter().map(|h| levels.get(h).cloned()).collect();
+    let Some(first) = all_levels.first() else {
+        if !*reported {
+            error!("No playable levels loaded");
+            *reported = true;
+        }
+        return;
+    };
+    let level_brief = all_levels.manifest.levels.iter().zip(all_levels.levels.iter()).map(|(entry, level)| {
+        match level {
+            Some(level) => format!("{}: {} polylines, {} stars", entry.title, level.polylines.len(), level.stars.len()),
+            None => format!("{}: failed to load", entry.title),
+        }
     }).collect::<Vec<_>>();
-    info!("Loaded {} levels:\n{:?}", all_levels.0.len(), level_brief);
+    info!("Loaded {} levels:\n{:?}", all_levels.levels.len(), level_brief);
     *current_level_state = LevelState {
-        id: 0,
-        stars: all_levels.0[0].stars.len(),
+        id: first,
+        stars: all_levels.levels[first].as_ref().map_or(0, |l| l.stars.len()),
     };
+    next_state.set(GameState::Menu);
+}
+
+pub fn hot_reload_levels(
+    mut events: EventReader<AssetEvent<Level>>,
+    levels: Res<Assets<Level>>,
+    level_handles: Res<LevelHandles>,
+    mut all_levels: ResMut<GameLevels>,
+    level_state: Res<LevelState>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in events.iter() {
+        let AssetEvent::Modified { handle } = event else { continue; };
+        let Some(id) = level_handles.levels.iter().position(|h| h == handle) else { continue; };
+        let (Some(level), Some(slot)) = (levels.get(handle), all_levels.levels.get_mut(id)) else { continue; };
+        info!("Level {} changed on disk, reloading", id);
+        *slot = Some(level.clone());
+        if id == level_state.id && matches!(state.get(), GameState::Loading | GameState::Playing) {
+            next_state.set(GameState::Reloading);
+        }
+    }
+}
+
+pub fn reload_level(
+    mut next_state: ResMut<NextState<GameState>>,
+    all_levels: Res<GameLevels>,
+    mut level_state: ResMut<LevelState>,
+) {
+    level_state.stars = all_levels.get(level_state.id).map_or(0, |l| l.stars.len());
+    next_state.set(GameState::Loading);
 }
 
 pub fn switch_level(
     mut next_state: ResMut<NextState<GameState>>,
     all_levels: Res<GameLevels>,
     mut level_state: ResMut<LevelState>,
-    q_scene: Query<&Visibility, With<ColliderType>>,
+    q_scene: Query<&Visibility, LevelScene>,
 ) {
     if q_scene.iter().filter(|v| **v == Visibility::Visible).count() > 0 {
         return;
     }
-    let state = *level_state;
-    if state.id < all_levels.0.len() - 1 {
-        info!("Switching to level {}", level_state.id + 1);
-        *level_state = LevelState {
-            id: state.id + 1,
-            stars: all_levels.0[state.id + 1].stars.len(),
-        };
-    } else {
-        info!("No more levels, replaying level 0");
-        *level_state = LevelState {
-            id: 0,
-            stars: all_levels.0[0].stars.len(),
-        };
-    }
+    let Some(id) = all_levels.next(level_state.id) else {
+        info!("No more levels, back to level select");
+        next_state.set(GameState::Menu);
+        return;
+    };
+    info!("Switching to level {}", id);
+    *level_state = LevelState {
+        id,
+        stars: all_levels.get(id).map_or(0, |l| l.stars.len()),
+    };
     next_state.set(GameState::Loading);
 }
 
 #[derive(Debug, Clone, Resource)]
 pub struct SlowLoadTimer(pub Timer);
 
+pub fn polyline_path(vertices: &[Vec2]) -> Path {
+    let mut path = PathBuilder::new();
+    if let Some(first) = vertices.first() { path.move_to(*first); }
+    for vertex in vertices.iter().skip(1) { path.line_to(*vertex); }
+    path.build()
+}
+
+pub fn spawn_scene_polyline<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    vertices: &[Vec2],
+    color: Color,
+    collider_type: ColliderType,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut entity = commands.spawn((
+        Collider::polyline(vertices.to_vec(), None),
+        ShapeBundle {
+            path: polyline_path(vertices),
+            ..default()
+        },
+        Stroke {
+            color,
+            options: StrokeOptions::default().with_line_width(LEVEL_SCENE_LINE_WIDTH).with_line_join(LineJoin::Round),
+        },
+        collider_type,
+    ));
+    entity.insert((
+        Transform::from_xyz(0., 0., SCENE_Z_INDEX),
+        Visibility::Hidden,
+    ));
+    entity
+}
+
 
 pub fn setup_current_level(
     all_levels: Res<GameLevels>,
@@ -137,58 +331,113 @@ pub fn setup_current_level(
 ) {
     info!("Set up level {} with stars {}", current_level_state.id, current_level_state.stars);
     let level_id = (*current_level_state).id;
+    let Some(level) = all_levels.get(level_id) else {
+        error!("Level {} is not loaded", level_id);
+        return;
+    };
+    if let Some(entry) = all_levels.entry(level_id) {
+        info!("Level {}: \"{}\" by {}", level_id, entry.title, entry.author);
+    }
 
-    for vertices in &all_levels.0[level_id].polylines {
+    for vertices in &level.polylines {
         if vertices.len() < 1 { continue; }
-        let mut path = PathBuilder::new();
-        path.move_to(vertices[0]);
-        for vertex in vertices.iter().skip(1) { path.line_to(*vertex); }
-        let polyline = path.build();
-        commands.spawn((
-            Collider::polyline(vertices.clone(), None),
-            ShapeBundle {
-                path: GeometryBuilder::build_as(
-                    &polyline,
-                ),
-                ..Default::default()
-            },
-            Stroke {
-                color: Color::BLACK, 
-                options: StrokeOptions::default().with_line_width(LEVEL_SCENE_LINE_WIDTH).with_line_join(LineJoin::Round),
-            },
-            ColliderType::Scene,
-        )).insert((
-            Transform::from_xyz(0., 0., SCENE_Z_INDEX),
-        )).insert(Visibility::Hidden);
+        spawn_scene_polyline(&mut commands, vertices, Color::BLACK, ColliderType::Scene);
     }
-
-    let stars = &all_levels.0[level_id].stars;
-    if stars.len() < 1 { return; }
-    for star in stars {
+    for vertices in &level.hazards {
+        if vertices.len() < 1 { continue; }
+        spawn_scene_polyline(&mut commands, vertices, HAZARD_COLOR, ColliderType::Hazard)
+            .insert(Sensor);
+    }
+    for vertices in &level.bouncy {
+        if vertices.len() < 1 { continue; }
+        spawn_scene_polyline(&mut commands, vertices, BOUNCY_COLOR, ColliderType::Scene)
+            .insert(Restitution {
+                coefficient: BOUNCY_RESTITUTION,
+                combine_rule: CoefficientCombineRule::Max,
+            });
+    }
+    for platform in &level.platforms {
+        if platform.vertices.len() < 1 { continue; }
+        spawn_scene_polyline(&mut commands, &platform.vertices, Color::BLACK, ColliderType::Scene)
+            .insert((
+                RigidBody::KinematicPositionBased,
+                PlatformMotion {
+                    offset: platform.offset,
+                    period: platform.period,
+                    elapsed: 0.,
+                },
+            ));
+    }
+    for goal in &level.goals {
         commands.spawn((
             ShapeBundle {
                 path: GeometryBuilder::build_as(&shapes::Circle {
-                    radius: STAR_RADIUS,
+                    radius: GOAL_RADIUS,
                     center: Vec2::ZERO,
                 }),
                 ..default()
             },
-            Collider::ball(STAR_RADIUS * 0.8),
-            Fill::color(Color::WHITE),
-            ColliderType::Star,
+            Collider::ball(GOAL_RADIUS * 0.8),
+            Stroke {
+                color: Color::BLACK,
+                options: StrokeOptions::default().with_line_width(LEVEL_SCENE_LINE_WIDTH),
+            },
+            ColliderType::Goal,
             ActiveEvents::COLLISION_EVENTS,
         ))
-        .insert(Transform::from_xyz(star.x, star.y, SCENE_Z_INDEX),)
+        .insert(Transform::from_xyz(goal.x, goal.y, SCENE_Z_INDEX))
         .insert(Visibility::Hidden)
         .insert(Sensor);
     }
+    for hint in &level.hints {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(hint.text.clone(), TextStyle {
+                    font_size: HINT_FONT_SIZE,
+                    color: Color::BLACK,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(hint.position.x, hint.position.y, SCENE_Z_INDEX),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            LevelHint,
+        ));
+    }
+
+    let stars = &level.stars;
+    if stars.len() < 1 { return; }
+    for star in stars {
+        spawn_star(&mut commands, *star)
+            .insert(Visibility::Hidden);
+    }
     info!("Level {} set up, playing", level_id);
 }
 
+pub fn spawn_star<'w, 's, 'a>(commands: &'a mut Commands<'w, 's>, position: Vec2) -> EntityCommands<'w, 's, 'a> {
+    let mut entity = commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: STAR_RADIUS,
+                center: Vec2::ZERO,
+            }),
+            ..default()
+        },
+        Collider::ball(STAR_RADIUS * 0.8),
+        Fill::color(Color::WHITE),
+        ColliderType::Star,
+        ActiveEvents::COLLISION_EVENTS,
+    ));
+    entity
+        .insert(Transform::from_xyz(position.x, position.y, SCENE_Z_INDEX),)
+        .insert(Sensor);
+    entity
+}
+
 pub fn slow_load_level(
     mut slow_load_timer: ResMut<SlowLoadTimer>,
     time: Res<Time>,
-    mut q_scene: Query<&mut Visibility, With<ColliderType>>,
+    mut q_scene: Query<&mut Visibility, LevelScene>,
 ) {
     if slow_load_timer.0.tick(time.delta()).just_finished() {
         for mut v in q_scene.iter_mut().filter(|v| **v == Visibility::Hidden).take(1) {
@@ -201,8 +450,13 @@ pub fn slow_clean_level(
     mut slow_load_timer: ResMut<SlowLoadTimer>,
     time: Res<Time>,
     mut q_scene: Query<(Entity, &Stroke, &mut Visibility), With<ColliderType>>,
+    mut q_unstroked: Query<&mut Visibility, (LevelScene, Without<Stroke>)>,
     mut commands: Commands,
 ) {
+    // hints and the like have nothing to fade
+    for mut v in q_unstroked.iter_mut() {
+        *v = Visibility::Hidden;
+    }
     if slow_load_timer.0.tick(time.delta()).just_finished() {
         for (e, s, mut v) in q_scene.iter_mut() {
             // shift the entity with a new alpha value
@@ -223,7 +477,7 @@ pub fn slow_clean_level(
 
 pub fn switch_playing(
     mut next_state: ResMut<NextState<GameState>>,
-    q_scene: Query<&Visibility, With<ColliderType>>,
+    q_scene: Query<&Visibility, LevelScene>,
 ) {
     // if all scenes and stars are visible, then playing
     if q_scene.iter().filter(|v| **v == Visibility::Hidden).count() > 0 {
@@ -241,6 +495,7 @@ pub fn collect_star(
     rapier_context: Res<RapierContext>,
 ) {
     let player = q_player.single();
+    let has_goal = q_stars.iter().any(|(_, t)| *t == ColliderType::Goal);
     
     /* Iterate through all the contact pairs involving a specific collider. */
     for (collider1, collider2, intersecting) in rapier_context.intersections_with(player) {
@@ -249,15 +504,20 @@ pub fn collect_star(
             info!("Player is intersecting with {:?}", other_collider);
             if let Ok((star, collider_type)) = q_stars.get(other_collider) {
                 info!("collider_type: {:?}", collider_type);
+                if *collider_type == ColliderType::Goal && level_state.stars == 0 {
+                    info!("Goal reached, switching level");
+                    next_state.set(GameState::Cleaning);
+                    return;
+                }
                 if *collider_type == ColliderType::Star {
-                    if level_state.stars <= 1 {
+                    if level_state.stars <= 1 && !has_goal {
                         level_state.stars -= 1;
                         info!("No more stars left, switching level");
                         commands.entity(star).despawn();
                         next_state.set(GameState::Cleaning);
                         return;
                     }
-                    level_state.stars -= 1;
+                    level_state.stars = level_state.stars.saturating_sub(1);
                     info!("Star collected! {} left", level_state.stars);
                     commands.entity(star).despawn();
                 }
@@ -266,11 +526,48 @@ pub fn collect_star(
     }
 }
 
+pub fn touch_hazard(
+    q_sensors: Query<&ColliderType, With<Sensor>>,
+    q_player: Query<Entity, With<PlayerStatus>>,
+    rapier_context: Res<RapierContext>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(player) = q_player.get_single() else { return; };
+    let hit = rapier_context.intersections_with(player).any(|(collider1, collider2, intersecting)| {
+        let other_collider = if collider1 == player { collider2 } else { collider1 };
+        intersecting && q_sensors.get(other_collider).is_ok_and(|t| *t == ColliderType::Hazard)
+    });
+    if hit {
+        info!("Player hit a hazard, back to start");
+        next_state.set(GameState::Failed);
+    }
+}
+
+pub fn move_platforms(
+    mut q_platforms: Query<(&mut PlatformMotion, &mut Transform)>,
+) {
+    for (mut motion, mut transform) in q_platforms.iter_mut() {
+        // once per step, so platforms are where they were at the same step whatever the frame rate
+        motion.elapsed += PHYSICS_TIMESTEP;
+        if motion.period <= 0. { continue; }
+        let phase = motion.elapsed / motion.period * std::f32::consts::TAU;
+        let position = motion.offset * (0.5 - 0.5 * phase.cos());
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
 pub fn clean_current_level(
     mut commands: Commands,
-    q_colliders: Query<Entity, With<ColliderType>>,
-    q_player: Query<Entity, With<PlayerStatus>>
+    q_colliders: Query<Entity, LevelScene>,
+    q_player: Query<Entity, With<PlayerStatus>>,
+    mut lines: ResMut<Lines>,
+    mut current_line: ResMut<PlayerCurrentLineEntity>,
+    mut history: ResMut<LineHistory>,
 ) {
+    lines.0.clear();
+    current_line.0 = None;
+    history.clear();
     for entity in q_colliders.iter() {
         commands.entity(entity).despawn_recursive();
     }
@@ -285,4 +582,44 @@ pub fn play_drawing_sound(
 ) {
     audio.play(asset_server.load("drawing.ogg"))
         .with_volume(0.3);
-}
\ No newline at end of file
+}
+#[derive(Debug, Resource, Default)]
+pub struct LevelMusic {
+    pub track: Option<String>,
+    pub instance: Option<Handle<AudioInstance>>,
+}
+
+pub fn play_level_music(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut level_music: ResMut<LevelMusic>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    let track = all_levels.entry(level_state.id).and_then(|e| e.music.clone());
+    if track == level_music.track {
+        return;
+    }
+    if let Some(instance) = level_music.instance.take().and_then(|h| audio_instances.get_mut(&h)) {
+        instance.stop(AudioTween::linear(Duration::from_secs(1)));
+    }
+    if let Some(track) = &track {
+        level_music.instance = Some(audio.play(asset_server.load(track.as_str()))
+            .looped()
+            .fade_in(AudioTween::new(Duration::from_secs(1), AudioEasing::OutPowi(2)))
+            .with_volume(LEVEL_MUSIC_VOLUME)
+            .handle());
+    }
+    level_music.track = track;
+}
+
+pub fn set_level_background(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    clear_color.0 = all_levels.entry(level_state.id)
+        .and_then(|e| e.background)
+        .unwrap_or(ClearColor::default().0);
+}
@@ -1,5 +1,5 @@
 #![allow(unused_parens)]
-use bevy::{prelude::*, render::texture::ImageSampler, window::{PresentMode::AutoVsync, WindowResolution}, utils::Duration};
+use bevy::{prelude::*, render::{camera::ScalingMode, texture::ImageSampler}, time::TimeUpdateStrategy, utils::Duration, window::{PresentMode::AutoVsync, WindowResolution}};
 use bevy_rapier2d::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use bevy_kira_audio::prelude::*;
@@ -8,9 +8,40 @@ use cfg_if::cfg_if;
 
 pub mod player;
 pub mod level;
+pub mod format;
+pub mod manifest;
+pub mod editor;
+pub mod history;
+pub mod ink;
+pub mod stroke;
+pub mod input;
+pub mod screen;
+pub mod camera;
+pub mod retry;
+pub mod progress;
+pub mod menu;
+pub mod sim;
+pub mod replay;
+pub mod physics;
+pub mod ghost;
 
 use player::*;
 use level::*;
+use manifest::*;
+use format::*;
+use editor::*;
+use history::*;
+use ink::*;
+use stroke::*;
+use input::*;
+use screen::*;
+use camera::*;
+use retry::*;
+use progress::*;
+use menu::*;
+use replay::*;
+use physics::*;
+use ghost::*;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -35,29 +66,98 @@ pub enum ColliderType {
     Ground,
     Scene,
     Star,
+    Hazard,
+    Goal,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, States, Default)]
 pub enum GameState {
     #[default]
+    LoadingAssets,
     Loading,
     Playing,
     Cleaning,
+    Reloading,
+    Editing,
+    /// the ball was lost, it's put back at the start
+    Failed,
+    /// level select
+    Menu,
 }
 
+/// The camera the level is seen through, cursor positions are converted to world space with it.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct MainCamera;
+
 pub const PIXELS_PER_METER: f32 = 100.0;
 
+/// How [`build_app`] sets the game up, [`AppConfig::default`] is the windowed game.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// no window, rendering or audio, for tests and tools
+    pub headless: bool,
+    /// advance time by exactly this much every update instead of following the wall clock
+    pub fixed_timestep: Option<Duration>,
+    /// play these levels, starting at the first, instead of loading the manifest from the assets folder
+    pub levels: Option<Vec<Level>>,
+    /// read and write the player's progress
+    pub persist_progress: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            headless: false,
+            fixed_timestep: None,
+            levels: None,
+            persist_progress: true,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Headless app taking one physics step per update, straight into the first of `levels`.
+    pub fn headless(levels: Vec<Level>) -> Self {
+        AppConfig {
+            headless: true,
+            fixed_timestep: Some(Duration::from_secs_f32(PHYSICS_TIMESTEP)),
+            levels: Some(levels),
+            persist_progress: false,
+        }
+    }
+}
+
 pub fn run_app() {
-    App::new()
-        .add_state::<GameState>()
-        .add_plugins((
+    build_app(AppConfig::default()).run();
+}
+
+pub fn build_app(config: AppConfig) -> App {
+    let mut app = App::new();
+    if config.headless {
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            bevy::input::InputPlugin,
+            AssetPlugin::default(),
+        ))
+        // rendering isn't there to add it
+        .insert_resource(ClearColor::default());
+    } else {
+        app.add_plugins((
             DefaultPlugins
+                .set(AssetPlugin {
+                    // hot reload levels, not supported on wasm
+                    #[cfg(not(target_arch = "wasm32"))]
+                    watch_for_changes: bevy::asset::ChangeWatcher::with_delay(bevy::utils::Duration::from_millis(200)),
+                    ..default()
+                })
                 .set(ImagePlugin {
                     default_sampler: ImageSampler::nearest_descriptor(),
                 })
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        resolution: WindowResolution::new(1280., 720.),
+                        resolution: WindowResolution::new(LOGICAL_WIDTH, LOGICAL_HEIGHT),
                         present_mode: AutoVsync,
                         // for wasm
                         fit_canvas_to_parent: true,
@@ -66,40 +166,131 @@ pub fn run_app() {
                     }),
                     ..default()
                 }),
-        ))
-        .add_plugins((
-            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER),
             AudioPlugin,
+            ShapePlugin,
         ))
-        .add_plugins(ShapePlugin)
+        .insert_resource(Msaa::Sample8)
+        .add_systems(OnEnter(GameState::Loading), (play_level_music, play_drawing_sound).run_if(in_state(GameState::Loading)));
+    }
+    if let Some(timestep) = config.fixed_timestep {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+    }
+    if config.persist_progress {
+        app.add_systems(Startup, load_progress)
+            .add_systems(Update, save_progress);
+    } else {
+        app.insert_resource(Progress::default());
+    }
+    match config.levels {
+        Some(levels) => {
+            let all_levels = GameLevels::from_levels(levels);
+            app.insert_resource(LevelState {
+                id: 0,
+                stars: all_levels.get(0).map_or(0, |l| l.stars.len()),
+            })
+            .insert_resource(all_levels)
+            .insert_resource(NextState(Some(GameState::Loading)));
+        }
+        None => {
+            app.insert_resource(LevelState::default())
+                .insert_resource(GameLevels::default())
+                .add_systems(Startup, load_all_levels)
+                .add_systems(Update, wait_for_levels.run_if(in_state(GameState::LoadingAssets)));
+        }
+    }
+    app.add_state::<GameState>()
+        // the physics sets are run in FixedUpdate below, around the gameplay of each step
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER).with_default_system_setup(false))
+        .insert_resource(physics_config())
+        .insert_resource(FixedTime::new_from_secs(PHYSICS_TIMESTEP))
+        .add_asset::<Level>()
+        .add_asset::<LevelManifest>()
+        .init_asset_loader::<LevelLoader>()
+        .init_asset_loader::<LevelManifestLoader>()
         .register_type::<PlayerCurrentLineEntity>()
         .register_type::<Lines>()
         .register_type::<LevelState>()
-        .insert_resource(Msaa::Sample8)
+        .register_type::<Ink>()
+        .register_type::<StrokeProcessing>()
+        .register_type::<StrokeStyle>()
+        .register_type::<CameraFollow>()
+        .register_type::<RetryConfig>()
+        .register_type::<Attempt>()
+        .register_type::<GhostConfig>()
+        // updated once per step instead of per frame, so none are dropped before a step reads them
+        .init_resource::<Events<DrawInput>>()
+        .init_resource::<Events<ReleaseGravity>>()
+        .init_resource::<Events<RestartLevel>>()
+        .add_event::<PlayerStatusChanged>()
         .insert_resource(PlayerCurrentLineEntity::default())
         .insert_resource(Lines::default())
-        .insert_resource(LevelState::default())
-        .insert_resource(GameLevels::default())
-        .add_systems(Startup, (setup_graphics, load_all_levels, setup_music, setup_slow_load))
-        .add_systems(OnEnter(GameState::Loading), (setup_current_level, play_drawing_sound).run_if(in_state(GameState::Loading)))
+        .insert_resource(LineHistory::default())
+        .insert_resource(Ink::default())
+        .insert_resource(StrokeProcessing::default())
+        .insert_resource(LevelHandles::default())
+        .insert_resource(LevelMusic::default())
+        .insert_resource(EditorLevel::default())
+        .insert_resource(EditorStroke::default())
+        .insert_resource(DrawTouches::default())
+        .insert_resource(GamepadCursor::default())
+        .insert_resource(CameraFollow::default())
+        .insert_resource(RetryConfig::default())
+        .insert_resource(Attempt::default())
+        .insert_resource(ReplayRecorder::default())
+        .insert_resource(ReplayPlayback::default())
+        .insert_resource(GhostConfig::default())
+        .insert_resource(GhostRecorder::default())
+        .add_systems(Startup, (setup_graphics, setup_letterbox, setup_slow_load, setup_ink_hud, setup_gamepad_cursor))
+        .add_systems(Update, (fit_viewport, hot_reload_levels, update_ink_hud, update_gamepad_cursor, save_replay))
+        .add_systems(OnEnter(GameState::Loading), (setup_current_level, set_level_background, reset_ink, reset_camera, start_attempt, start_replay, clear_step_inputs).run_if(in_state(GameState::Loading)))
         .add_systems(Update, (slow_load_level, switch_playing).run_if(in_state(GameState::Loading)))
-        .add_systems(OnEnter(GameState::Playing), (spawn_player,))
-        .add_systems(Update, (set_gravity, mouse_draw, collect_star).run_if(in_state(GameState::Playing)))
+        .add_systems(OnEnter(GameState::Playing), (spawn_player, spawn_ghost))
+        .add_systems(OnExit(GameState::Playing), (despawn_ghost,))
+        .configure_sets(FixedUpdate, (
+            FixedStepSet::Act,
+            PhysicsSet::SyncBackend,
+            PhysicsSet::SyncBackendFlush,
+            PhysicsSet::StepSimulation,
+            PhysicsSet::Writeback,
+            FixedStepSet::React,
+        ).chain())
+        .add_systems(FixedUpdate, (
+            RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend).in_set(PhysicsSet::SyncBackend),
+            RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackendFlush).in_set(PhysicsSet::SyncBackendFlush),
+            RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::StepSimulation).in_set(PhysicsSet::StepSimulation),
+            RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::Writeback).in_set(PhysicsSet::Writeback),
+        ))
+        .add_systems(FixedUpdate, (play_replay, (record_replay, set_gravity, mouse_draw, restart_level, move_platforms).after(play_replay), record_ghost.after(set_gravity).after(restart_level)).in_set(FixedStepSet::Act).run_if(playing_step))
+        // strokes drawn and stars put back this step are there for Rapier to step with
+        .add_systems(FixedUpdate, apply_deferred.after(FixedStepSet::Act).before(PhysicsSet::SyncBackend))
+        // the last star or goal is checked after the failures, finishing wins when the ball is lost on the same step
+        .add_systems(FixedUpdate, ((update_player_status, check_failure, touch_hazard, collect_star).chain(), store_step_positions, advance_replay_clock).in_set(FixedStepSet::React).run_if(playing_step))
+        .add_systems(FixedUpdate, (Events::<DrawInput>::update_system, Events::<ReleaseGravity>::update_system, Events::<RestartLevel>::update_system).after(FixedStepSet::React))
+        .add_systems(Update, (play_ghost, toggle_ghost).run_if(in_state(GameState::Playing)))
+        .add_systems(Update, (read_mouse_input, read_touch_input, read_gamepad_input, read_undo_input, read_gravity_input, read_restart_input).run_if(in_state(GameState::Playing)).run_if(not_replaying))
+        .add_systems(Update, (follow_player, interpolate_shapes).run_if(in_state(GameState::Playing)))
         .add_systems(Update, (slow_clean_level, switch_level).run_if(in_state(GameState::Cleaning)))
         .add_systems(OnExit(GameState::Cleaning), (clean_current_level,))
-        .run();
+        .add_systems(OnEnter(GameState::Reloading), (clean_current_level,))
+        .add_systems(Update, reload_level.run_if(in_state(GameState::Reloading)))
+        .add_systems(Update, (open_editor, open_menu, play_saved_replay).run_if(in_state(GameState::Playing)))
+        .add_systems(OnEnter(GameState::Failed), (retry_level, count_retry))
+        .add_systems(OnEnter(GameState::Cleaning), (record_completion, finish_replay, record_ghost_run))
+        .add_systems(OnEnter(GameState::Menu), (clean_current_level, setup_menu))
+        .add_systems(Update, select_level.run_if(in_state(GameState::Menu)))
+        .add_systems(OnExit(GameState::Menu), (exit_menu,))
+        .add_systems(OnEnter(GameState::Editing), (clean_current_level, enter_editor))
+        .add_systems(Update, (edit_level, draw_editor_level, draw_editor_stroke, save_edited_level, play_test_level).chain().run_if(in_state(GameState::Editing)))
+        .add_systems(OnExit(GameState::Editing), (exit_editor,));
+    app
 }
 
 
 fn setup_graphics(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
-}
-
-fn setup_music(asset_server: Res<AssetServer>, audio: Res<Audio>) {
-    audio.play(asset_server.load("Tokyo Ghoul：re OST - Mvt.11 “Memories”.ogg"))
-        .looped()
-        .fade_in(AudioTween::new(Duration::from_secs(1), AudioEasing::OutPowi((2))))
-        .with_volume(0.62);
+    let mut camera = Camera2dBundle::default();
+    // levels are authored in pixels of the logical play area, whatever the window size
+    camera.projection.scaling_mode = ScalingMode::Fixed { width: LOGICAL_WIDTH, height: LOGICAL_HEIGHT };
+    commands.spawn((camera, MainCamera));
 }
 
 fn setup_slow_load(mut commands: Commands) {
@@ -0,0 +1,49 @@
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset}, reflect::{TypePath, TypeUuid}, utils::BoxedFuture};
+use serde::{Deserialize, Serialize};
+
+pub static LEVEL_MANIFEST_FILE: &str = "levels/levels.manifest.ron";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelEntry {
+    /// level file, relative to the levels folder
+    pub file: String,
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    /// expected number of strokes to finish the level
+    #[serde(default)]
+    pub par: Option<u32>,
+    #[serde(default)]
+    pub background: Option<Color>,
+    /// music track, relative to the assets folder
+    #[serde(default)]
+    pub music: Option<String>,
+}
+
+/// Ordered list of levels, the game plays them in this order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "3f5e2a4d-8c61-4b7e-a0d2-9e4b6c1f7a38"]
+pub struct LevelManifest {
+    pub levels: Vec<LevelEntry>,
+}
+
+#[derive(Debug, Default)]
+pub struct LevelManifestLoader;
+
+impl AssetLoader for LevelManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let manifest = ron::de::from_bytes::<LevelManifest>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(manifest));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["manifest.ron"]
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use crate::{GameState, GameLevels, LevelState, Progress, Ink};
+
+pub static MENU_KEY: KeyCode = KeyCode::Escape;
+pub static MENU_COLUMNS: usize = 4;
+pub static MENU_TITLE_FONT_SIZE: f32 = 48.;
+pub static MENU_BUTTON_FONT_SIZE: f32 = 20.;
+pub static MENU_BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.3);
+pub static MENU_BUTTON_HOVER_COLOR: Color = Color::rgb(0.35, 0.35, 0.42);
+pub static MENU_BUTTON_LOCKED_COLOR: Color = Color::rgb(0.12, 0.12, 0.12);
+
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct MenuEntity;
+
+/// A level in the select grid, locked ones can't be picked.
+#[derive(Debug, Component, Clone, Copy)]
+pub struct LevelButton {
+    pub id: usize,
+    pub unlocked: bool,
+}
+
+pub fn open_menu(
+    keyboard: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(MENU_KEY) {
+        info!("Back to level select");
+        next_state.set(GameState::Menu);
+    }
+}
+
+fn button_label(all_levels: &GameLevels, progress: &Progress, id: usize, unlocked: bool) -> String {
+    let title = all_levels.entry(id).map_or("", |entry| entry.title.as_str());
+    if !unlocked {
+        return format!("{}\nlocked", id + 1);
+    }
+    let mut label = format!("{}\n{}", id + 1, title);
+    if let Some(record) = progress.record(all_levels, id).filter(|r| r.completed) {
+        label.push_str("\n★");
+        if let Some(strokes) = record.best_strokes {
+            label.push_str(&format!(" {} strokes", strokes));
+            if let Some(par) = all_levels.entry(id).and_then(|entry| entry.par) {
+                label.push_str(&format!(" (par {})", par));
+            }
+        }
+        if let Some(ink) = record.best_ink {
+            label.push_str(&format!("\n{:.0} ink", ink));
+        }
+    }
+    label
+}
+
+pub fn setup_menu(
+    all_levels: Res<GameLevels>,
+    progress: Res<Progress>,
+    mut ink: ResMut<Ink>,
+    mut commands: Commands,
+) {
+    // no level, no ink to show
+    *ink = Ink::default();
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(24.),
+                ..default()
+            },
+            ..default()
+        },
+        MenuEntity,
+    ))
+    .with_children(|root| {
+        root.spawn(TextBundle::from_section("Select a level", TextStyle {
+            font_size: MENU_TITLE_FONT_SIZE,
+            color: Color::BLACK,
+            ..default()
+        }));
+        root.spawn(NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: RepeatedGridTrack::px(MENU_COLUMNS as u16, 200.),
+                column_gap: Val::Px(12.),
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|grid| {
+            for id in 0..all_levels.levels.len() {
+                let unlocked = progress.is_unlocked(&all_levels, id);
+                grid.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            height: Val::Px(110.),
+                            padding: UiRect::all(Val::Px(8.)),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: if unlocked { MENU_BUTTON_COLOR } else { MENU_BUTTON_LOCKED_COLOR }.into(),
+                        ..default()
+                    },
+                    LevelButton { id, unlocked },
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(button_label(&all_levels, &progress, id, unlocked), TextStyle {
+                        font_size: MENU_BUTTON_FONT_SIZE,
+                        color: Color::WHITE,
+                        ..default()
+                    }).with_text_alignment(TextAlignment::Center));
+                });
+            }
+        });
+    });
+}
+
+pub fn exit_menu(
+    mut commands: Commands,
+    q_menu: Query<Entity, With<MenuEntity>>,
+) {
+    for entity in q_menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn select_level(
+    all_levels: Res<GameLevels>,
+    mut level_state: ResMut<LevelState>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut q_buttons: Query<(&Interaction, &LevelButton, &mut BackgroundColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut color) in q_buttons.iter_mut() {
+        if !button.unlocked {
+            continue;
+        }
+        match interaction {
+            Interaction::Pressed => {
+                info!("Selected level {}", button.id);
+                *level_state = LevelState {
+                    id: button.id,
+                    stars: all_levels.get(button.id).map_or(0, |l| l.stars.len()),
+                };
+                next_state.set(GameState::Loading);
+            }
+            Interaction::Hovered => *color = MENU_BUTTON_HOVER_COLOR.into(),
+            Interaction::None => *color = MENU_BUTTON_COLOR.into(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::GameState;
+
+/// Length of a physics step, the game advances by whole steps whatever the frame rate.
+pub static PHYSICS_TIMESTEP: f32 = 1. / 60.;
+
+/// Gameplay run in `FixedUpdate` around each Rapier step, so it happens between the same steps
+/// every run, at 144Hz, in a throttled tab or headless.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum FixedStepSet {
+    /// inputs, gravity release and platforms, before the step
+    Act,
+    /// contacts, failure checks then stars, after it
+    React,
+}
+
+/// One step of [`PHYSICS_TIMESTEP`] per fixed update.
+pub fn physics_config() -> RapierConfiguration {
+    RapierConfiguration {
+        timestep_mode: TimestepMode::Fixed {
+            dt: PHYSICS_TIMESTEP,
+            substeps: 1,
+        },
+        ..default()
+    }
+}
+
+/// Steps go on while the level plays, but not once an earlier step of the frame has ended it:
+/// the state only changes between frames.
+pub fn playing_step(state: Res<State<GameState>>, next_state: Res<NextState<GameState>>) -> bool {
+    *state.get() == GameState::Playing && next_state.0.is_none()
+}
+
+/// The ball's position at the last two steps, its shape is drawn in between.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct RenderInterpolation {
+    pub previous: Vec2,
+    pub current: Vec2,
+}
+
+impl RenderInterpolation {
+    /// No streak from where it was, after being spawned or moved by hand.
+    pub fn teleport(position: Vec2) -> Self {
+        RenderInterpolation { previous: position, current: position }
+    }
+
+    /// Where the body is drawn, `alpha` of the way from the last step to the one before the next.
+    pub fn at(&self, alpha: f32) -> Vec2 {
+        self.previous.lerp(self.current, alpha)
+    }
+}
+
+/// How far the frame is between the last step and the next one.
+pub fn interpolation_alpha(fixed_time: &FixedTime) -> f32 {
+    (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.)
+}
+
+/// The shape of an interpolated body, a child so the body keeps its simulated transform.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct InterpolatedShape;
+
+pub fn store_step_positions(mut q_bodies: Query<(&Transform, &mut RenderInterpolation)>) {
+    for (transform, mut interpolation) in q_bodies.iter_mut() {
+        interpolation.previous = interpolation.current;
+        interpolation.current = transform.translation.truncate();
+    }
+}
+
+/// Offsets the shapes of the bodies to where they're drawn this frame.
+pub fn interpolate_shapes(
+    fixed_time: Res<FixedTime>,
+    q_bodies: Query<(&RenderInterpolation, &Transform, &Children), Without<InterpolatedShape>>,
+    mut q_shapes: Query<&mut Transform, With<InterpolatedShape>>,
+) {
+    let alpha = interpolation_alpha(&fixed_time);
+    for (interpolation, body_transform, children) in q_bodies.iter() {
+        let offset = interpolation.at(alpha) - interpolation.current;
+        // the offset is in world space, the shape in the rotating body's
+        let local = body_transform.rotation.inverse() * offset.extend(0.);
+        for child in children.iter() {
+            if let Ok(mut transform) = q_shapes.get_mut(*child) {
+                transform.translation = local.truncate().extend(transform.translation.z);
+            }
+        }
+    }
+}
@@ -3,7 +3,7 @@ use bevy::{prelude::*, window::PrimaryWindow, utils::{HashMap, tracing::level_fi
 use bevy_rapier2d::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 
-use crate::{ColliderType, GameLevels, LevelState, level, GameState};
+use crate::{ColliderType, GameLevels, LevelState, level, GameState, LineHistory, LineEdit, Ink, StrokeProcessing, polyline_length, polyline_path, cut_polyline, DrawInput, ReleaseGravity, MainCamera, Attempt, RenderInterpolation, InterpolatedShape};
 
 pub static PLAYER_RADIUS: f32 = 25.0;
 pub static PLAYER_GRAVITY_SCALE: f32 = 9.8;
@@ -12,6 +12,8 @@ pub static PLAYER_DRAW_DISTANCE_TO_BALL_THRESHOLD: f32 = 25.;
 pub static PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD: f32 = 5.8;
 pub static PLAYER_ERASE_DISTANCE_THRESHOLD: f32 = 20.;
 pub static PLAYER_DRAW_Z_INDEX: f32 = 1.;
+// slower than this on the ground counts as still, in pixels per second
+pub static PLAYER_STILL_SPEED: f32 = 5.;
 
 #[derive(Debug, Component, Clone, Copy, Default, PartialEq, Reflect)]
 #[reflect(Component)]
@@ -22,6 +24,13 @@ pub enum PlayerStatus {
     Falling,
 }
 
+/// Sent when the ball switches between [`PlayerStatus`] variants, speed changes while moving aren't.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct PlayerStatusChanged {
+    pub from: PlayerStatus,
+    pub to: PlayerStatus,
+}
+
 #[derive(Resource, PartialEq, Debug, Clone, Copy, Reflect)]
 #[reflect(Resource)]
 pub struct PlayerCurrentLineEntity(pub Option<Entity>);
@@ -42,187 +51,222 @@ impl Default for Lines {
     }
 }
 
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct LineSegment;
+
+/// Capsule collider covering the segment `a`-`b` of a drawn line, placed by its transform.
+pub fn line_segment_collider(a: Vec2, b: Vec2, width: f32) -> (Collider, Transform) {
+    let collider = if a.distance_squared(b) > f32::EPSILON {
+        Collider::capsule(Vec2::ZERO, b - a, width / 2.0)
+    } else {
+        Collider::ball(width / 2.0)
+    };
+    (collider, Transform::from_xyz(a.x, a.y, 0.))
+}
+
+/// Spawns one collider per segment as children of a line, so a growing line only adds
+/// colliders for its new segments instead of rebuilding the whole shape.
+pub fn spawn_line_segments(children: &mut ChildBuilder, vertices: &[Vec2], width: f32) {
+    if vertices.len() == 1 {
+        let (collider, transform) = line_segment_collider(vertices[0], vertices[0], width);
+        children.spawn((collider, TransformBundle::from(transform), LineSegment));
+    }
+    for w in vertices.windows(2) {
+        let (collider, transform) = line_segment_collider(w[0], w[1], width);
+        children.spawn((collider, TransformBundle::from(transform), LineSegment));
+    }
+}
+
+/// How a stroke looks, kept on the stroke entity so later edits match it.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct StrokeStyle {
+    pub color: Color,
+    pub width: f32,
+    pub z_index: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle {
+            color: Color::GRAY,
+            width: PLAYER_DRAW_LINE_WIDTH,
+            z_index: PLAYER_DRAW_Z_INDEX,
+        }
+    }
+}
+
+/// The stroke entity itself, its colliders and round caps are children, see [`spawn_stroke`].
+#[derive(Bundle)]
+pub struct StrokeBundle {
+    pub shape: ShapeBundle,
+    pub stroke: Stroke,
+    pub style: StrokeStyle,
+    pub collider_type: ColliderType,
+}
+
+impl StrokeBundle {
+    pub fn new(vertices: &[Vec2], style: StrokeStyle) -> Self {
+        StrokeBundle {
+            shape: ShapeBundle {
+                path: polyline_path(vertices),
+                transform: Transform::from_xyz(0., 0., style.z_index),
+                ..default()
+            },
+            stroke: Stroke {
+                color: style.color,
+                options: StrokeOptions::default().with_line_width(style.width).with_line_join(LineJoin::Round),
+            },
+            style,
+            collider_type: ColliderType::Ground,
+        }
+    }
+}
+
+fn spawn_stroke_cap(children: &mut ChildBuilder, center: Vec2, style: StrokeStyle) {
+    children.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Circle {
+                radius: style.width / 2.0,
+                center,
+            }),
+            ..default()
+        },
+        Fill::color(style.color),
+    ));
+}
+
+fn spawn_stroke_children(children: &mut ChildBuilder, vertices: &[Vec2], style: StrokeStyle) {
+    // an empty stroke has nothing to roll on
+    let (Some(first), Some(last)) = (vertices.first(), vertices.last()) else { return; };
+    spawn_line_segments(children, vertices, style.width);
+    // round head and tail
+    spawn_stroke_cap(children, *first, style);
+    if vertices.len() > 1 {
+        spawn_stroke_cap(children, *last, style);
+    }
+}
+
+/// Spawns a stroke along `vertices` the player can roll on, returning its entity.
+pub fn spawn_stroke(commands: &mut Commands, vertices: &[Vec2], style: StrokeStyle) -> Entity {
+    commands.spawn(StrokeBundle::new(vertices, style))
+        .with_children(|children| spawn_stroke_children(children, vertices, style))
+        .id()
+}
+
+/// Appends the segment `from`-`to` to a stroke whose path already ends at `to`.
+pub fn extend_stroke(commands: &mut Commands, entity: Entity, from: Vec2, to: Vec2, style: StrokeStyle) {
+    // only the new segment needs a collider, the rest of the line keeps its own
+    commands.entity(entity).with_children(|children| {
+        spawn_line_segments(children, &[from, to], style.width);
+        spawn_stroke_cap(children, to, style);
+    });
+}
+
+/// Replaces the shape, caps and colliders of an existing stroke with `vertices`.
+pub fn rebuild_stroke(commands: &mut Commands, entity: Entity, path: &mut Path, vertices: &[Vec2], style: StrokeStyle) {
+    if vertices.is_empty() { return; }
+    *path = polyline_path(vertices);
+    commands.entity(entity)
+        .despawn_descendants()
+        .with_children(|children| spawn_stroke_children(children, vertices, style));
+}
+
+/// Converts a window position (origin top left, y down) to world coordinates as seen by the camera,
+/// so it follows panning, zoom and letterboxing. Positions outside the camera's viewport give `None`.
+pub fn cursor_to_world(camera: &Camera, camera_transform: &GlobalTransform, position: Vec2) -> Option<Vec2> {
+    let offset = camera.viewport.as_ref()
+        .and_then(|viewport| camera.to_logical(viewport.physical_position))
+        .unwrap_or(Vec2::ZERO);
+    let position = position - offset;
+    let size = camera.logical_viewport_size()?;
+    if position.cmplt(Vec2::ZERO).any() || position.cmpgt(size).any() {
+        return None;
+    }
+    camera.viewport_to_world_2d(camera_transform, position)
+}
+
 pub fn mouse_draw(
-    buttons: Res<Input<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut draw_input: EventReader<DrawInput>,
     q_player: Query<&Transform, With<PlayerStatus>>,
-    mut q_path: Query<(&mut Path, &mut Collider), With<ColliderType>>,
+    mut q_path: Query<(&mut Path, &StrokeStyle)>,
     mut current_line_hid: ResMut<PlayerCurrentLineEntity>,
     mut lines: ResMut<Lines>,
+    mut history: ResMut<LineHistory>,
+    mut ink: ResMut<Ink>,
+    processing: Res<StrokeProcessing>,
+    mut attempt: ResMut<Attempt>,
+    mut last_erase: Local<Option<Vec2>>,
     mut commands: Commands,
 ) {
-    let center_x = q_windows.single().width() / 2.0;
-    let center_y = q_windows.single().height() / 2.0;
-    if let Some(position) = q_windows.single().cursor_position() {
-        let (mouse_x, mouse_y) = (position.x - center_x, center_y - position.y);
-        let vec_mouse = Vec2::from([mouse_x, mouse_y]);
-        let player_translation = q_player.get_single().unwrap().translation;
-        if vec_mouse.distance(Vec2::from([player_translation.x, player_translation.y])) < PLAYER_DRAW_DISTANCE_TO_BALL_THRESHOLD {
-            return;
-        }
-        if buttons.just_pressed(MouseButton::Left) {
-            let mut path_builder = PathBuilder::new();
-            path_builder.move_to(vec_mouse);
-            let new_path = path_builder.build();
-
-            let new_path_entity = commands.spawn((
-                Collider::compound([(vec_mouse, 0., Collider::ball(PLAYER_DRAW_LINE_WIDTH / 2.0))].to_vec()),
-                ShapeBundle {
-                    path: GeometryBuilder::build_as(&new_path),
-                    ..default()
-                },
-                Stroke {
-                    color: Color::GRAY,
-                    options: StrokeOptions::default().with_line_width(PLAYER_DRAW_LINE_WIDTH).with_line_join(LineJoin::Round),
-                },
-                ColliderType::Ground,
-            ))
-            .insert(Transform::from_xyz(0., 0., PLAYER_DRAW_Z_INDEX))
-            // spawn round head
-            .with_children(|children| {
-                children.spawn((
-                    ShapeBundle {
-                        path: GeometryBuilder::build_as(&shapes::Circle {
-                            radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                            center: vec_mouse,
-                        }),
-                        ..default()
-                    },
-                    Fill::color(Color::GRAY),
-                ));
-            })
-            .id();
+    let Ok(player_transform) = q_player.get_single() else { return; };
+    let player_position = player_transform.translation.truncate();
+    for input in draw_input.iter() {
+        match *input {
+            // ends are handled even over the ball, otherwise the line would never finish
+            DrawInput::End => {
+                info!("Released mouse");
+                if let Some(handle) = current_line_hid.0.take() {
+                    if let (Some(vertices), Ok((mut path, style))) = (lines.0.get_mut(&handle), q_path.get_mut(handle)) {
+                        let processed = processing.process(vertices);
+                        ink.refund(polyline_length(vertices) - polyline_length(&processed));
+                        rebuild_stroke(&mut commands, handle, &mut path, &processed, *style);
+                        *vertices = processed;
+                    }
+                }
+            }
+            DrawInput::EraseEnd => {
+                history.finish_erase();
+                *last_erase = None;
+            }
+            // don't pull a line from under the mouse, a finger or the gamepad cursor while it's being drawn
+            DrawInput::Undo | DrawInput::Redo if current_line_hid.0.is_some() => {}
+            DrawInput::Undo | DrawInput::Redo => {
+                history.undo_redo(*input == DrawInput::Redo, &mut lines, &mut ink, &mut commands);
+            }
+            DrawInput::Begin(position) | DrawInput::Extend(position) | DrawInput::EraseAt(position)
+                if position.distance(player_position) < PLAYER_DRAW_DISTANCE_TO_BALL_THRESHOLD => {}
+            DrawInput::Begin(position) => {
+                if ink.remaining() <= 0. {
+                    continue;
+                }
+                let new_path_entity = spawn_stroke(&mut commands, &[position], StrokeStyle::default());
 
-            *lines.0.entry(new_path_entity).or_insert_with(Vec::new) = vec![vec_mouse];
+                lines.0.insert(new_path_entity, vec![position]);
+                history.record(LineEdit {
+                    removed: Vec::new(),
+                    added: vec![new_path_entity],
+                });
 
-            info!("Spawned line with id: {:?}", new_path_entity);
-            (*current_line_hid).0 = Some(new_path_entity);
-            return;  // the bevy entity update will not be called until the next frame, so we need to return here
-        }
-        if buttons.pressed(MouseButton::Right) {
-            // erase lines
-            let mut new_vertices_hm = lines.0.clone();
-            for (e, polyline) in lines.0.clone().into_iter() {
-                // spawn one half
-                let mut despawn_original_entity = false;
-                let mut start_index = 0;
-                for i in 0..polyline.len() {
-                    if polyline[i].distance(vec_mouse) < PLAYER_ERASE_DISTANCE_THRESHOLD { 
-                        despawn_original_entity = true;
-                        if i - start_index > 1 {
-                            let new_line_vertices = polyline[start_index..i].to_vec();
-                            info!("original line length {}", polyline.len());
-                            info!("new line [{}..{}]", start_index, i);
-                            let mut new_line_path = PathBuilder::new();
-                            new_line_path.move_to(new_line_vertices[0]);
-                            for v in new_line_vertices.iter().skip(0) {
-                                new_line_path.line_to(*v);
-                            }
-                            let new_line_path = new_line_path.build();
-                            let new_polyline_entity = commands.spawn((
-                                Collider::compound(new_line_vertices.iter()
-                                    .map(|v| (*v, 0., Collider::ball(PLAYER_DRAW_LINE_WIDTH / 2.0)))
-                                    .collect::<Vec<_>>()),
-                                ShapeBundle {
-                                    path: GeometryBuilder::build_as(&new_line_path),
-                                    ..default()
-                                },
-                                Stroke {
-                                    color: Color::GRAY,
-                                    options: StrokeOptions::default().with_line_width(PLAYER_DRAW_LINE_WIDTH).with_line_join(LineJoin::Round),
-                                },
-                                ColliderType::Ground,
-                            ))
-                            .insert(Transform::from_xyz(0., 0., PLAYER_DRAW_Z_INDEX))
-                            // spawn round head and tail
-                            .with_children(|children| {
-                                children.spawn((
-                                    ShapeBundle {
-                                        path: GeometryBuilder::build_as(&shapes::Circle {
-                                            radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                                            center: *new_line_vertices.first().unwrap_or(&vec_mouse),
-                                        }),
-                                        ..default()
-                                    },
-                                    Fill::color(Color::GRAY),
-                                ));
-                            })
-                            .with_children(|children| {
-                                children.spawn((
-                                    ShapeBundle {
-                                        path: GeometryBuilder::build_as(&shapes::Circle {
-                                            radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                                            center: *new_line_vertices.last().unwrap_or(&vec_mouse),
-                                        }),
-                                        ..default()
-                                    },
-                                    Fill::color(Color::GRAY),
-                                ));
-                            })
-                            
-                            .id();
-                            new_vertices_hm.insert(new_polyline_entity, new_line_vertices);
-                        }
-                        start_index = i + 1;
-                        info!("start index {}", start_index);
-                        if start_index >= polyline.len() { break; }
+                attempt.strokes += 1;
+                info!("Spawned line with id: {:?}", new_path_entity);
+                (*current_line_hid).0 = Some(new_path_entity);
+                // the bevy entity update will not be called until the next frame, extending waits for it
+                return;
+            }
+            DrawInput::EraseAt(position) => {
+                // erase everything the cursor swept over since the last frame
+                let from = last_erase.unwrap_or(position);
+                *last_erase = Some(position);
+                for (e, polyline) in lines.0.clone().into_iter() {
+                    let Some(pieces) = cut_polyline(&polyline, from, position, PLAYER_ERASE_DISTANCE_THRESHOLD) else {
+                        continue;
+                    };
+                    let style = q_path.get(e).map_or_else(|_| StrokeStyle::default(), |(_, style)| *style);
+                    let mut added = Vec::new();
+                    let mut kept_length = 0.;
+                    for piece in pieces {
+                        let new_polyline_entity = spawn_stroke(&mut commands, &piece, style);
+                        kept_length += polyline_length(&piece);
+                        lines.0.insert(new_polyline_entity, piece);
+                        added.push(new_polyline_entity);
                     }
-                }
-                // spawn the rest
-                if start_index + 1 < polyline.len() && despawn_original_entity == true {
-                    let new_line_vertices = polyline[start_index..].to_vec();
-                    let mut new_line_path = PathBuilder::new();
-                    new_line_path.move_to(new_line_vertices[0]);
-                    for v in new_line_vertices.iter().skip(0) {
-                        new_line_path.line_to(*v);
+                    ink.refund(polyline_length(&polyline) - kept_length);
+                    history.record_erase((e, polyline), added);
+                    lines.0.remove(&e);
+                    if current_line_hid.0 == Some(e) {
+                        current_line_hid.0 = None;
                     }
-                    let new_line_path = new_line_path.build();
-                    let new_polyline_entity = commands.spawn((
-                        Collider::compound(new_line_vertices.iter()
-                            .map(|v| (*v, 0., Collider::ball(PLAYER_DRAW_LINE_WIDTH / 2.0)))
-                            .collect::<Vec<_>>()),
-                        ShapeBundle {
-                            path: GeometryBuilder::build_as(&new_line_path),
-                            ..default()
-                        },
-                        Stroke {
-                            color: Color::GRAY,
-                            options: StrokeOptions::default().with_line_width(PLAYER_DRAW_LINE_WIDTH).with_line_join(LineJoin::Round),
-                        },
-                        ColliderType::Ground,
-                    ))
-                    .insert((
-                        Transform::from_xyz(0., 0., PLAYER_DRAW_Z_INDEX),
-                    ))
-                    // spawn round head and tail
-                    .with_children(|children| {
-                        children.spawn((
-                            ShapeBundle {
-                                path: GeometryBuilder::build_as(&shapes::Circle {
-                                    radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                                    center: *new_line_vertices.first().unwrap_or(&vec_mouse),
-                                }),
-                                ..default()
-                            },
-                            Fill::color(Color::GRAY),
-                        ));
-                    })
-                    .with_children(|children| {
-                        children.spawn((
-                            ShapeBundle {
-                                path: GeometryBuilder::build_as(&shapes::Circle {
-                                    radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                                    center: *new_line_vertices.last().unwrap_or(&vec_mouse),
-                                }),
-                                ..default()
-                            },
-                            Fill::color(Color::GRAY),
-                        ));
-                    }).id();
-                    new_vertices_hm.insert(new_polyline_entity, new_line_vertices);
-                }
-                if despawn_original_entity == true {
-                    new_vertices_hm.remove(&e);
                     if let Some(e) = commands.get_entity(e) {
                         e.despawn_recursive();
                     } else {
@@ -230,58 +274,31 @@ pub fn mouse_draw(
                     }
                 }
             }
-            lines.0 = new_vertices_hm;
-            return;
-        }
-        if buttons.pressed(MouseButton::Left) {
-            if let Some(handle) = (*current_line_hid).0 {
-                if let Ok((mut old_path, mut collider)) = q_path.get_mut(handle) {
-                    // build new shape
-                    let new_line_shape = (*lines).0.get_mut(&handle).ok_or_else(|| {
-                        info!("Failed to get path for entity: {:?}", handle);
-                        return;
-                    }).unwrap();
-                    let last_endpoint = new_line_shape.last().unwrap();
-                    if vec_mouse.distance(*last_endpoint) < PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD {
-                        return;
-                    }
-
-                    new_line_shape.push(vec_mouse);
-                    let mut new_path = PathBuilder::new();
-                    new_path.move_to(new_line_shape[0]);
-                    for v in new_line_shape.iter().skip(0) {
-                        new_path.line_to(*v);
-                    }
-                    let new_path = new_path.build();
-                    *old_path = ShapePath::new().add(&new_path).build();
-                    *collider = Collider::compound(new_line_shape.iter().map(|v| (*v, 0., Collider::ball(PLAYER_DRAW_LINE_WIDTH / 2.0))).collect::<Vec<_>>());
-                    // spawn round tail
-                    commands.entity(handle).with_children(|children| {
-                        children.spawn((
-                            ShapeBundle {
-                                path: GeometryBuilder::build_as(&shapes::Circle {
-                                    radius: PLAYER_DRAW_LINE_WIDTH / 2.0,
-                                    center: vec_mouse,
-                                }),
-                                ..default()
-                            },
-                            Fill::color(Color::GRAY),
-                        ));
-                    });
-                } else { 
+            DrawInput::Extend(position) => {
+                let Some(handle) = current_line_hid.0 else { continue; };
+                let (Some(new_line_shape), Ok((mut old_path, style))) = (lines.0.get_mut(&handle), q_path.get_mut(handle)) else {
                     info!("Failed to get path for entity: {:?}", handle);
-                    (*current_line_hid).0 = None;
-                    return;
+                    current_line_hid.0 = None;
+                    continue;
                 };
-            } else { return; }
-        } else if buttons.just_released(MouseButton::Left) {
-            info!("Released mouse");
-            (*current_line_hid).0 = None;
+                let last_endpoint = *new_line_shape.last().unwrap();
+                let segment_length = position.distance(last_endpoint);
+                if segment_length < PLAYER_DRAW_VERTICES_DISTANCE_THRESHOLD {
+                    continue;
+                }
+                // out of ink, the line stops where it runs out
+                let length = segment_length.min(ink.remaining());
+                if length <= 0. || !ink.spend(length) {
+                    continue;
+                }
+                let position = last_endpoint.lerp(position, length / segment_length);
+
+                new_line_shape.push(position);
+                *old_path = polyline_path(new_line_shape);
+                extend_stroke(&mut commands, handle, last_endpoint, position, *style);
+            }
         }
-    } else {
-        return;
     }
-
 }
 
 pub fn spawn_player(
@@ -290,19 +307,14 @@ pub fn spawn_player(
     mut commands: Commands
 ) {
     let level_id = (*current_level).id;
-    let player_start_position = (*all_levels).0[level_id].player;
+    let Some(level) = all_levels.get(level_id) else { return; };
+    let player_start_position = level.player;
     commands
         .spawn((
-            ShapeBundle {
-                path: GeometryBuilder::build_as(&shapes::Circle {
-                    radius: PLAYER_RADIUS,
-                    center: Vec2::ZERO,
-                }),
-                ..default()
-            },
-            Fill::color(Color::WHITE),
+            SpatialBundle::from_transform(Transform::from_xyz(player_start_position.x, player_start_position.y, 0.0)),
             RigidBody::Dynamic,
             PlayerStatus::default(),
+            RenderInterpolation::teleport(player_start_position),
         ))
         .insert((
             Collider::ball(PLAYER_RADIUS),
@@ -310,23 +322,67 @@ pub fn spawn_player(
             ActiveEvents::COLLISION_EVENTS,
             Restitution::coefficient(0.8),
             Friction::default(),
-            TransformBundle::from(Transform::from_xyz(player_start_position.x, player_start_position.y, 0.0)),
             Velocity::default(),
             GravityScale(0.0),
             Sleeping::disabled(),
             Ccd::enabled(),
-        ));
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&shapes::Circle {
+                        radius: PLAYER_RADIUS,
+                        center: Vec2::ZERO,
+                    }),
+                    ..default()
+                },
+                Fill::color(Color::WHITE),
+                InterpolatedShape,
+            ));
+        });
 }
 
 
 pub fn set_gravity(
-    keyboard: Res<Input<KeyCode>>,
+    mut release: EventReader<ReleaseGravity>,
     mut gravity: Query<(&mut GravityScale, &mut Velocity), With<PlayerStatus>>,
 ) {
-    if keyboard.just_pressed(KeyCode::Space) {
-        info!("pressed space");
-        let (mut g, mut v) = gravity.single_mut();
-        (*g).0 = PLAYER_GRAVITY_SCALE;
-        (*v).linvel.y = -0.1;
+    if release.iter().count() == 0 {
+        return;
+    }
+    let Ok((mut g, mut v)) = gravity.get_single_mut() else { return; };
+    info!("pressed space");
+    (*g).0 = PLAYER_GRAVITY_SCALE;
+    (*v).linvel.y = -0.1;
+}
+
+pub fn update_player_status(
+    rapier_context: Res<RapierContext>,
+    q_sensors: Query<(), With<Sensor>>,
+    mut q_player: Query<(Entity, &mut PlayerStatus, &Velocity, &GravityScale)>,
+    mut status_changed: EventWriter<PlayerStatusChanged>,
+) {
+    for (player, mut status, velocity, gravity) in q_player.iter_mut() {
+        let grounded = rapier_context.contacts_with(player).any(|pair| {
+            let other = if pair.collider1() == player { pair.collider2() } else { pair.collider1() };
+            pair.has_any_active_contacts() && !q_sensors.contains(other)
+        });
+        let speed = velocity.linvel.length();
+        let new_status = if gravity.0 == 0. {
+            // still hanging where it spawned
+            PlayerStatus::Still
+        } else if !grounded {
+            PlayerStatus::Falling
+        } else if speed > PLAYER_STILL_SPEED {
+            PlayerStatus::Moving(speed)
+        } else {
+            PlayerStatus::Still
+        };
+        if std::mem::discriminant(&*status) != std::mem::discriminant(&new_status) {
+            status_changed.send(PlayerStatusChanged { from: *status, to: new_status });
+        }
+        if *status != new_status {
+            *status = new_status;
+        }
     }
-}
\ No newline at end of file
+}
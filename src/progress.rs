@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameLevels, LevelState, Ink, GhostRun};
+
+/// Where progress is kept, a file next to the game on native builds, a `localStorage` key on the web.
+pub static PROGRESS_FILE: &str = "progress.ron";
+pub static PROGRESS_STORAGE_KEY: &str = "moon.progress";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelRecord {
+    pub completed: bool,
+    /// fewest strokes drawn in a finished run
+    pub best_strokes: Option<u32>,
+    /// least ink used in a finished run
+    pub best_ink: Option<f32>,
+    /// times the level was started, retries included
+    pub attempts: u32,
+}
+
+/// Records of every level played, keyed by the level file so reordering the manifest keeps them.
+#[derive(Debug, Clone, Default, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub levels: BTreeMap<String, LevelRecord>,
+    /// best run of each level, shown as a ghost
+    pub ghosts: BTreeMap<String, GhostRun>,
+}
+
+impl Progress {
+    pub fn record(&self, all_levels: &GameLevels, id: usize) -> Option<&LevelRecord> {
+        all_levels.entry(id).and_then(|entry| self.levels.get(&entry.file))
+    }
+
+    fn record_mut(&mut self, all_levels: &GameLevels, id: usize) -> Option<&mut LevelRecord> {
+        let entry = all_levels.entry(id)?;
+        Some(self.levels.entry(entry.file.clone()).or_default())
+    }
+
+    pub fn ghost(&self, all_levels: &GameLevels, id: usize) -> Option<&GhostRun> {
+        all_levels.entry(id).and_then(|entry| self.ghosts.get(&entry.file))
+    }
+
+    pub fn set_ghost(&mut self, all_levels: &GameLevels, id: usize, run: GhostRun) {
+        if let Some(entry) = all_levels.entry(id) {
+            self.ghosts.insert(entry.file.clone(), run);
+        }
+    }
+
+    /// The first playable level is always open, the others once the playable level before
+    /// them in the manifest is completed.
+    pub fn is_unlocked(&self, all_levels: &GameLevels, id: usize) -> bool {
+        if all_levels.get(id).is_none() {
+            return false;
+        }
+        match (0..id).rev().find(|i| all_levels.get(*i).is_some()) {
+            Some(previous) => self.record(all_levels, previous).is_some_and(|r| r.completed),
+            None => true,
+        }
+    }
+
+    pub fn load() -> Self {
+        let Some(text) = read_storage() else { return Progress::default(); };
+        ron::from_str(&text).unwrap_or_else(|e| {
+            error!("Failed to read progress, starting over: {}", e);
+            Progress::default()
+        })
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => write_storage(&text),
+            Err(e) => error!("Failed to serialize progress: {}", e),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_storage() -> Option<String> {
+    std::fs::read_to_string(PROGRESS_FILE).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_storage(text: &str) {
+    if let Err(e) = std::fs::write(PROGRESS_FILE, text) {
+        error!("Failed to save progress to {}: {}", PROGRESS_FILE, e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_storage() -> Option<String> {
+    local_storage()?.get_item(PROGRESS_STORAGE_KEY).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_storage(text: &str) {
+    let saved = local_storage().map_or(false, |storage| storage.set_item(PROGRESS_STORAGE_KEY, text).is_ok());
+    if !saved {
+        error!("Failed to save progress to localStorage");
+    }
+}
+
+/// Strokes drawn since the level was last started, or its lines cleared.
+#[derive(Debug, Clone, Copy, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Attempt {
+    pub strokes: u32,
+}
+
+pub fn load_progress(mut commands: Commands) {
+    commands.insert_resource(Progress::load());
+}
+
+pub fn save_progress(progress: Res<Progress>) {
+    if progress.is_changed() && !progress.is_added() {
+        progress.save();
+    }
+}
+
+pub fn start_attempt(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut attempt: ResMut<Attempt>,
+    mut progress: ResMut<Progress>,
+) {
+    *attempt = Attempt::default();
+    if let Some(record) = progress.record_mut(&all_levels, level_state.id) {
+        record.attempts += 1;
+    }
+}
+
+pub fn record_completion(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    attempt: Res<Attempt>,
+    ink: Res<Ink>,
+    mut progress: ResMut<Progress>,
+) {
+    let Some(record) = progress.record_mut(&all_levels, level_state.id) else { return; };
+    record.completed = true;
+    record.best_strokes = Some(record.best_strokes.map_or(attempt.strokes, |best| best.min(attempt.strokes)));
+    record.best_ink = Some(record.best_ink.map_or(ink.used, |best| best.min(ink.used)));
+    info!("Level {} finished with {} strokes, {:.0} ink", level_state.id, attempt.strokes, ink.used);
+}
+
+pub fn count_retry(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut progress: ResMut<Progress>,
+) {
+    if let Some(record) = progress.record_mut(&all_levels, level_state.id) {
+        record.attempts += 1;
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt::{self, Write};
+
+use bevy::prelude::*;
+
+use crate::{GameState, GameLevels, LevelState, DrawInput, ReleaseGravity, RestartLevel};
+
+/// Latest version of the replay format, written by [`Replay::to_text`].
+pub static REPLAY_FORMAT_VERSION: u32 = 1;
+pub static REPLAY_FILE: &str = "last.replay";
+pub static REPLAY_SAVE_KEY: KeyCode = KeyCode::F5;
+pub static REPLAY_PLAY_KEY: KeyCode = KeyCode::F6;
+
+/// Something the player did, replayed through the same events the input systems send.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayAction {
+    Draw(DrawInput),
+    ReleaseGravity,
+    Restart { clear_lines: bool },
+}
+
+/// An action and when it happened, in physics steps taken while the level was played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayInput {
+    pub tick: u32,
+    pub action: ReplayAction,
+}
+
+/// Every input given to a level, from when it was loaded, retries and restarts included.
+///
+/// Inputs are handled in the fixed step they were recorded at, before Rapier's, so it plays back
+/// the same run at any frame rate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    /// file of the level in the manifest
+    pub level: String,
+    pub inputs: Vec<ReplayInput>,
+}
+
+/// Error returned by [`Replay::parse`], `line` is 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayParseError {
+    pub line: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ReplayParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: unexpected `{}`", self.line, self.token)
+    }
+}
+
+impl std::error::Error for ReplayParseError {}
+
+fn parse_vec2(token: &str) -> Option<Vec2> {
+    let (x, y) = token.split_once(',')?;
+    Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+}
+
+impl Replay {
+    /// Reads the text format:
+    ///
+    /// ```text
+    /// v 1
+    /// f welcome.txt
+    /// 12 b -100,20
+    /// 13 e -90,18
+    /// 20 u
+    /// 31 g
+    /// ```
+    ///
+    /// after the version and level file, one input per line: the tick, then `b x,y`, `e x,y` and `u`
+    /// to begin, extend and end a stroke, `x x,y` and `xu` to erase, `g` to release the ball,
+    /// `r` to restart and `rc` to restart clearing the lines.
+    pub fn parse(source: &str) -> Result<Self, ReplayParseError> {
+        let mut replay = Replay::default();
+        for (line_index, line) in source.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') { continue; }
+            let mut tokens = trimmed.split_whitespace();
+            let first = tokens.next().unwrap_or_default();
+            let error = |token: &str| ReplayParseError { line: line_index + 1, token: token.to_string() };
+            match first {
+                "v" => {
+                    let version = tokens.next().unwrap_or_default();
+                    if version.parse::<u32>().map_or(true, |v| v == 0 || v > REPLAY_FORMAT_VERSION) {
+                        return Err(error(version));
+                    }
+                }
+                "f" => replay.level = tokens.next().ok_or_else(|| error(""))?.to_string(),
+                tick => {
+                    let tick = tick.parse::<u32>().map_err(|_| error(tick))?;
+                    let kind = tokens.next().unwrap_or_default();
+                    let mut position = || {
+                        let token = tokens.next().unwrap_or_default();
+                        parse_vec2(token).ok_or_else(|| error(token))
+                    };
+                    let action = match kind {
+                        "b" => ReplayAction::Draw(DrawInput::Begin(position()?)),
+                        "e" => ReplayAction::Draw(DrawInput::Extend(position()?)),
+                        "u" => ReplayAction::Draw(DrawInput::End),
+                        "x" => ReplayAction::Draw(DrawInput::EraseAt(position()?)),
+                        "xu" => ReplayAction::Draw(DrawInput::EraseEnd),
+                        "z" => ReplayAction::Draw(DrawInput::Undo),
+                        "zr" => ReplayAction::Draw(DrawInput::Redo),
+                        "g" => ReplayAction::ReleaseGravity,
+                        "r" => ReplayAction::Restart { clear_lines: false },
+                        "rc" => ReplayAction::Restart { clear_lines: true },
+                        _ => return Err(error(kind)),
+                    };
+                    replay.inputs.push(ReplayInput { tick, action });
+                }
+            }
+            if let Some(token) = tokens.next() {
+                return Err(error(token));
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Writes the replay in the latest text format, [`Replay::parse`] reads it back unchanged.
+    pub fn write_text(&self, w: &mut impl Write) -> fmt::Result {
+        writeln!(w, "v {}", REPLAY_FORMAT_VERSION)?;
+        writeln!(w, "f {}", self.level)?;
+        for input in &self.inputs {
+            write!(w, "{} ", input.tick)?;
+            match input.action {
+                ReplayAction::Draw(DrawInput::Begin(v)) => writeln!(w, "b {},{}", v.x, v.y)?,
+                ReplayAction::Draw(DrawInput::Extend(v)) => writeln!(w, "e {},{}", v.x, v.y)?,
+                ReplayAction::Draw(DrawInput::End) => writeln!(w, "u")?,
+                ReplayAction::Draw(DrawInput::EraseAt(v)) => writeln!(w, "x {},{}", v.x, v.y)?,
+                ReplayAction::Draw(DrawInput::EraseEnd) => writeln!(w, "xu")?,
+                ReplayAction::Draw(DrawInput::Undo) => writeln!(w, "z")?,
+                ReplayAction::Draw(DrawInput::Redo) => writeln!(w, "zr")?,
+                ReplayAction::ReleaseGravity => writeln!(w, "g")?,
+                ReplayAction::Restart { clear_lines: false } => writeln!(w, "r")?,
+                ReplayAction::Restart { clear_lines: true } => writeln!(w, "rc")?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        self.write_text(&mut text).expect("writing to a String can't fail");
+        text
+    }
+}
+
+/// Records the level being played, `tick` counts the physics steps taken in [`GameState::Playing`].
+#[derive(Debug, Clone, Default, Resource)]
+pub struct ReplayRecorder {
+    pub replay: Replay,
+    pub tick: u32,
+    /// the last level that was finished
+    pub finished: Option<Replay>,
+}
+
+/// Replay to play on the next level loaded, and the one playing.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct ReplayPlayback {
+    pub queued: Option<Replay>,
+    pub playing: Option<Replay>,
+    /// index of the next input of `playing` to send
+    pub next: usize,
+}
+
+pub fn start_replay(
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut playback: ResMut<ReplayPlayback>,
+) {
+    recorder.replay = Replay {
+        level: all_levels.entry(level_state.id).map(|entry| entry.file.clone()).unwrap_or_default(),
+        inputs: Vec::new(),
+    };
+    recorder.tick = 0;
+    playback.playing = playback.queued.take();
+    playback.next = 0;
+    if let Some(replay) = &playback.playing {
+        if replay.level != recorder.replay.level {
+            warn!("Replay was recorded on {} but {} is loaded, it may play out differently", replay.level, recorder.replay.level);
+        }
+        info!("Playing back a replay of {} with {} inputs", replay.level, replay.inputs.len());
+    }
+}
+
+/// The devices are only read while no replay plays, its inputs would get mixed with theirs.
+pub fn not_replaying(playback: Res<ReplayPlayback>) -> bool {
+    playback.playing.is_none()
+}
+
+/// Sends the replayed inputs for this step, before the input systems' events are handled.
+pub fn play_replay(
+    recorder: Res<ReplayRecorder>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut draw_input: EventWriter<DrawInput>,
+    mut release: EventWriter<ReleaseGravity>,
+    mut restart: EventWriter<RestartLevel>,
+) {
+    let playback = &mut *playback;
+    let Some(replay) = &playback.playing else { return; };
+    while let Some(input) = replay.inputs.get(playback.next).filter(|input| input.tick <= recorder.tick) {
+        match input.action {
+            ReplayAction::Draw(input) => draw_input.send(input),
+            ReplayAction::ReleaseGravity => release.send(ReleaseGravity),
+            ReplayAction::Restart { clear_lines } => restart.send(RestartLevel { clear_lines }),
+        }
+        playback.next += 1;
+    }
+}
+
+pub fn record_replay(
+    mut draw_input: EventReader<DrawInput>,
+    mut release: EventReader<ReleaseGravity>,
+    mut restart: EventReader<RestartLevel>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let tick = recorder.tick;
+    let actions = draw_input.iter().map(|input| ReplayAction::Draw(*input))
+        .chain(release.iter().map(|_| ReplayAction::ReleaseGravity))
+        .chain(restart.iter().map(|r| ReplayAction::Restart { clear_lines: r.clear_lines }))
+        .collect::<Vec<_>>();
+    recorder.replay.inputs.extend(actions.into_iter().map(|action| ReplayInput { tick, action }));
+}
+
+pub fn advance_replay_clock(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.tick += 1;
+}
+
+pub fn finish_replay(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.finished = Some(recorder.replay.clone());
+}
+
+/// `F5` saves the level being played, or the last one finished once it's over.
+pub fn save_replay(
+    keyboard: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    recorder: Res<ReplayRecorder>,
+) {
+    if !keyboard.just_pressed(REPLAY_SAVE_KEY) {
+        return;
+    }
+    let replay = match (state.get(), &recorder.finished) {
+        (GameState::Playing | GameState::Failed, _) | (_, None) => &recorder.replay,
+        (_, Some(finished)) => finished,
+    };
+    write_replay(&replay.to_text());
+}
+
+/// `F6` plays the replay saved with `F5` back, on the level it was recorded on.
+pub fn play_saved_replay(
+    keyboard: Res<Input<KeyCode>>,
+    all_levels: Res<GameLevels>,
+    mut level_state: ResMut<LevelState>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard.just_pressed(REPLAY_PLAY_KEY) {
+        return;
+    }
+    let Some(text) = read_replay() else { return; };
+    let replay = match Replay::parse(&text) {
+        Ok(replay) => replay,
+        Err(e) => {
+            error!("Failed to parse {}: {}", REPLAY_FILE, e);
+            return;
+        }
+    };
+    let Some(id) = all_levels.manifest.levels.iter().position(|entry| entry.file == replay.level) else {
+        error!("No level {} to play the replay on", replay.level);
+        return;
+    };
+    if all_levels.get(id).is_none() {
+        error!("Level {} failed to load, can't play the replay on it", replay.level);
+        return;
+    }
+    level_state.id = id;
+    playback.queued = Some(replay);
+    next_state.set(GameState::Reloading);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_replay() -> Option<String> {
+    match std::fs::read_to_string(REPLAY_FILE) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            error!("Failed to read replay from {}: {}", REPLAY_FILE, e);
+            None
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_replay() -> Option<String> {
+    info!("No replay files to play on the web");
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_replay(text: &str) {
+    match std::fs::write(REPLAY_FILE, text) {
+        Ok(()) => info!("Saved replay to {}", REPLAY_FILE),
+        Err(e) => error!("Failed to save replay to {}: {}", REPLAY_FILE, e),
+    }
+}
+
+// no files in the browser, it's logged to the console to be copied from there
+#[cfg(target_arch = "wasm32")]
+fn write_replay(text: &str) {
+    info!("Replay:\n{}", text);
+}
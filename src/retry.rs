@@ -0,0 +1,159 @@
+use bevy::{prelude::*, ecs::system::SystemParam};
+use bevy_rapier2d::prelude::*;
+
+use crate::{GameState, PHYSICS_TIMESTEP, GameLevels, LevelState, PlayerStatus, Lines, PlayerCurrentLineEntity, LineHistory, Ink, ColliderType, RestartLevel, RenderInterpolation, Level, Attempt, spawn_star};
+
+/// When a released ball counts as lost and what a retry keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct RetryConfig {
+    /// seconds the released ball may sit still before the level is retried, never when `None`
+    pub idle_timeout: Option<f32>,
+    /// how far past the level bounds the ball may go before it's lost
+    pub out_of_bounds_margin: f32,
+    /// clear the player's lines on retry instead of keeping them
+    pub clear_strokes: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            idle_timeout: Some(4.),
+            out_of_bounds_margin: 100.,
+            clear_strokes: false,
+        }
+    }
+}
+
+pub fn check_failure(
+    config: Res<RetryConfig>,
+    all_levels: Res<GameLevels>,
+    level_state: Res<LevelState>,
+    q_player: Query<(&Transform, &PlayerStatus, &GravityScale)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut idle: Local<f32>,
+) {
+    let Ok((transform, status, gravity)) = q_player.get_single() else { return; };
+    let Some(level) = all_levels.get(level_state.id) else { return; };
+    // nothing can go wrong before the ball is let go
+    if gravity.0 == 0. {
+        *idle = 0.;
+        return;
+    }
+    let bounds = level.world_bounds();
+    let position = transform.translation.truncate();
+    let margin = Vec2::splat(config.out_of_bounds_margin);
+    if position.cmplt(bounds.min - margin).any() || position.cmpgt(bounds.max + margin).any() {
+        info!("Player left the level, retrying");
+        *idle = 0.;
+        next_state.set(GameState::Failed);
+        return;
+    }
+    if *status == PlayerStatus::Still {
+        *idle += PHYSICS_TIMESTEP;
+    } else {
+        *idle = 0.;
+    }
+    if config.idle_timeout.is_some_and(|timeout| *idle >= timeout) {
+        info!("Player stopped for {:.1}s, retrying", *idle);
+        *idle = 0.;
+        next_state.set(GameState::Failed);
+    }
+}
+
+/// The player's lines, and everything counted from them.
+#[derive(SystemParam)]
+pub struct PlayerLines<'w> {
+    lines: ResMut<'w, Lines>,
+    current_line: ResMut<'w, PlayerCurrentLineEntity>,
+    history: ResMut<'w, LineHistory>,
+    ink: ResMut<'w, Ink>,
+    attempt: ResMut<'w, Attempt>,
+}
+
+impl PlayerLines<'_> {
+    /// Removes every line the player drew and gives the ink back, the strokes count starts over.
+    pub fn clear(&mut self, commands: &mut Commands) {
+        for (entity, _) in self.lines.0.drain() {
+            if let Some(entity) = commands.get_entity(entity) {
+                entity.despawn_recursive();
+            }
+        }
+        self.current_line.0 = None;
+        self.history.clear();
+        self.ink.used = 0.;
+        *self.attempt = Attempt::default();
+    }
+}
+
+/// The level being played and its stars.
+#[derive(SystemParam)]
+pub struct LevelStars<'w, 's> {
+    all_levels: Res<'w, GameLevels>,
+    level_state: ResMut<'w, LevelState>,
+    q_stars: Query<'w, 's, (Entity, &'static ColliderType)>,
+}
+
+impl LevelStars<'_, '_> {
+    pub fn level(&self) -> Option<&Level> {
+        self.all_levels.get(self.level_state.id)
+    }
+
+    /// Puts back the stars collected since the level started, a new try has to collect them again.
+    pub fn reset(&mut self, commands: &mut Commands) {
+        let Some(level) = self.all_levels.get(self.level_state.id) else { return; };
+        for (entity, collider_type) in self.q_stars.iter() {
+            if *collider_type == ColliderType::Star {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for star in &level.stars {
+            spawn_star(commands, *star);
+        }
+        self.level_state.stars = level.stars.len();
+    }
+}
+
+/// Takes the ball away, back in [`GameState::Playing`] it's spawned again at the level's start.
+pub fn retry_level(
+    config: Res<RetryConfig>,
+    q_player: Query<Entity, With<PlayerStatus>>,
+    mut stars: LevelStars,
+    mut player_lines: PlayerLines,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    for entity in q_player.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    stars.reset(&mut commands);
+    if config.clear_strokes {
+        player_lines.clear(&mut commands);
+    }
+    next_state.set(GameState::Playing);
+}
+
+/// Starts the level over in place: the ball back at its spawn and every star back, `clear_lines`
+/// also clears the player's lines.
+pub fn restart_level(
+    mut restart: EventReader<RestartLevel>,
+    mut q_player: Query<(&mut Transform, &mut Velocity, &mut GravityScale, &mut RenderInterpolation), With<PlayerStatus>>,
+    mut stars: LevelStars,
+    mut player_lines: PlayerLines,
+    mut commands: Commands,
+) {
+    let Some(clear_lines) = restart.iter().last().map(|r| r.clear_lines) else { return; };
+    let Some(start) = stars.level().map(|level| level.player) else { return; };
+    info!("Restarting level {}", stars.level_state.id);
+    for (mut transform, mut velocity, mut gravity, mut interpolation) in q_player.iter_mut() {
+        transform.translation = start.extend(0.);
+        transform.rotation = Quat::IDENTITY;
+        *velocity = Velocity::zero();
+        gravity.0 = 0.;
+        *interpolation = RenderInterpolation::teleport(start);
+    }
+    stars.reset(&mut commands);
+    if clear_lines {
+        player_lines.clear(&mut commands);
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::{
+    prelude::*,
+    core_pipeline::clear_color::ClearColorConfig,
+    render::{camera::Viewport, view::RenderLayers},
+    window::PrimaryWindow,
+};
+
+use crate::MainCamera;
+
+/// Size of the play area levels are authored for, scaled to fit the window.
+pub static LOGICAL_WIDTH: f32 = 1280.;
+pub static LOGICAL_HEIGHT: f32 = 720.;
+pub static LETTERBOX_COLOR: Color = Color::BLACK;
+// only the letterbox camera renders this layer
+pub static LETTERBOX_LAYER: u8 = 1;
+
+/// One of the two bars filling the window around the play area, `side` is -1 or 1.
+#[derive(Debug, Component, Clone, Copy)]
+pub struct LetterboxBar {
+    pub side: f32,
+}
+
+pub fn logical_size() -> Vec2 {
+    Vec2::new(LOGICAL_WIDTH, LOGICAL_HEIGHT)
+}
+
+pub fn setup_letterbox(mut commands: Commands) {
+    // drawn over the main camera: its clear covers the whole window, not only its viewport
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        UiCameraConfig { show_ui: false },
+        RenderLayers::layer(LETTERBOX_LAYER),
+    ));
+    for side in [-1., 1.] {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: LETTERBOX_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+            LetterboxBar { side },
+            RenderLayers::layer(LETTERBOX_LAYER),
+        ));
+    }
+}
+
+/// Fits the largest play area with the logical aspect ratio in the window, centered,
+/// and covers the rest with the letterbox bars.
+pub fn fit_viewport(
+    q_windows: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut q_camera: Query<&mut Camera, With<MainCamera>>,
+    mut q_bars: Query<(&mut Sprite, &mut Transform, &LetterboxBar)>,
+) {
+    let Ok(window) = q_windows.get_single() else { return; };
+    let physical = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    let scale = (physical.x / LOGICAL_WIDTH).min(physical.y / LOGICAL_HEIGHT);
+    let size = logical_size() * scale;
+    for mut camera in q_camera.iter_mut() {
+        camera.viewport = Some(Viewport {
+            physical_position: ((physical - size) / 2.).as_uvec2(),
+            physical_size: size.as_uvec2().max(UVec2::ONE),
+            ..default()
+        });
+    }
+
+    // the letterbox camera works in logical window pixels, centered
+    let window_size = Vec2::new(window.width(), window.height());
+    let area = size / window.scale_factor() as f32;
+    let pillarbox = window_size.x - area.x > window_size.y - area.y;
+    for (mut sprite, mut transform, bar) in q_bars.iter_mut() {
+        let (bar_size, offset) = if pillarbox {
+            let width = (window_size.x - area.x) / 2.;
+            (Vec2::new(width, window_size.y), Vec2::new((area.x + width) / 2., 0.))
+        } else {
+            let height = (window_size.y - area.y) / 2.;
+            (Vec2::new(window_size.x, height), Vec2::new(0., (area.y + height) / 2.))
+        };
+        sprite.custom_size = Some(bar_size);
+        transform.translation = (offset * bar.side).extend(0.);
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::{prelude::*, ecs::system::CommandQueue};
+
+use crate::{
+    AppConfig, GameState, Level, LevelState, Lines, PlayerStatus, StrokeStyle, DrawInput, ReleaseGravity,
+    Replay, ReplayRecorder, ReplayPlayback, build_app, spawn_stroke,
+};
+
+/// A headless game stepped one fixed update at a time, for tests and tools.
+pub struct Simulation {
+    pub app: App,
+}
+
+impl Simulation {
+    /// Plays `levels` headless, starting at the first one.
+    pub fn new(levels: Vec<Level>) -> Self {
+        Simulation::with_config(AppConfig::headless(levels))
+    }
+
+    pub fn with_config(config: AppConfig) -> Self {
+        let mut app = build_app(config);
+        // what `App::run` would do before the first update
+        app.finish();
+        app.cleanup();
+        Simulation { app }
+    }
+
+    pub fn tick(&mut self) {
+        self.app.update();
+    }
+
+    pub fn state(&self) -> GameState {
+        self.app.world.resource::<State<GameState>>().get().clone()
+    }
+
+    /// Ticks until `done` holds, returning how many ticks that took, or `None` after `max_ticks`.
+    pub fn run_until(&mut self, max_ticks: u32, mut done: impl FnMut(&mut Simulation) -> bool) -> Option<u32> {
+        for tick in 0..max_ticks {
+            if done(self) {
+                return Some(tick);
+            }
+            self.tick();
+        }
+        done(self).then_some(max_ticks)
+    }
+
+    /// Ticks through the level's load animation until the ball is there.
+    pub fn wait_for_playing(&mut self, max_ticks: u32) -> Option<u32> {
+        self.run_until(max_ticks, |sim| sim.state() == GameState::Playing && sim.player_position().is_some())
+    }
+
+    /// Adds a finished line, as if the player had drawn it, without spending ink.
+    pub fn add_line(&mut self, vertices: &[Vec2]) -> Entity {
+        let mut queue = CommandQueue::default();
+        let entity = spawn_stroke(&mut Commands::new(&mut queue, &self.app.world), vertices, StrokeStyle::default());
+        queue.apply(&mut self.app.world);
+        self.app.world.resource_mut::<Lines>().0.insert(entity, vertices.to_vec());
+        entity
+    }
+
+    /// Sends drawing input, handled on the next tick like the mouse or a touch would be.
+    pub fn draw(&mut self, input: DrawInput) {
+        self.app.world.resource_mut::<Events<DrawInput>>().send(input);
+    }
+
+    pub fn release_gravity(&mut self) {
+        self.app.world.resource_mut::<Events<ReleaseGravity>>().send(ReleaseGravity);
+    }
+
+    /// Plays `replay` on the next level loaded, queue it before the first tick to replay the first level.
+    pub fn queue_replay(&mut self, replay: Replay) {
+        self.app.world.resource_mut::<ReplayPlayback>().queued = Some(replay);
+    }
+
+    /// Inputs given to the level being played so far.
+    pub fn recording(&self) -> &Replay {
+        &self.app.world.resource::<ReplayRecorder>().replay
+    }
+
+    pub fn stars_left(&self) -> usize {
+        self.app.world.resource::<LevelState>().stars
+    }
+
+    pub fn player_position(&mut self) -> Option<Vec2> {
+        self.app.world.query_filtered::<&Transform, With<PlayerStatus>>()
+            .get_single(&self.app.world)
+            .ok()
+            .map(|transform| transform.translation.truncate())
+    }
+}
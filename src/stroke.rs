@@ -0,0 +1,312 @@
+use bevy::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum Smoothing {
+    None,
+    /// corner cutting, each iteration doubles the vertices and rounds corners off
+    Chaikin { iterations: u32 },
+    /// spline through the vertices, `samples` points per segment
+    CatmullRom { samples: u32 },
+}
+
+/// Post-processing applied to a drawn line once the mouse is released.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct StrokeProcessing {
+    /// Ramer–Douglas–Peucker tolerance in pixels, `0` keeps every vertex
+    pub simplify_tolerance: f32,
+    pub smoothing: Smoothing,
+}
+
+impl Default for StrokeProcessing {
+    fn default() -> Self {
+        StrokeProcessing {
+            simplify_tolerance: 1.5,
+            smoothing: Smoothing::Chaikin { iterations: 2 },
+        }
+    }
+}
+
+impl StrokeProcessing {
+    pub fn process(&self, vertices: &[Vec2]) -> Vec<Vec2> {
+        let simplified = if self.simplify_tolerance > 0. {
+            simplify_rdp(vertices, self.simplify_tolerance)
+        } else {
+            vertices.to_vec()
+        };
+        match self.smoothing {
+            Smoothing::None => simplified,
+            Smoothing::Chaikin { iterations } => smooth_chaikin(&simplified, iterations),
+            Smoothing::CatmullRom { samples } => smooth_catmull_rom(&simplified, samples),
+        }
+    }
+}
+
+pub fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared <= f32::EPSILON {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / length_squared).clamp(0., 1.);
+    p.distance(a + ab * t)
+}
+
+pub fn simplify_rdp(vertices: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if vertices.len() < 3 {
+        return vertices.to_vec();
+    }
+    let mut keep = vec![false; vertices.len()];
+    keep[0] = true;
+    keep[vertices.len() - 1] = true;
+    let mut ranges = vec![(0, vertices.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let (index, distance) = (start + 1..end)
+            .map(|i| (i, distance_to_segment(vertices[i], vertices[start], vertices[end])))
+            .fold((start, 0.), |best, d| if d.1 > best.1 { d } else { best });
+        if distance > tolerance {
+            keep[index] = true;
+            ranges.push((start, index));
+            ranges.push((index, end));
+        }
+    }
+    vertices.iter().zip(keep).filter(|(_, k)| *k).map(|(v, _)| *v).collect()
+}
+
+pub fn smooth_chaikin(vertices: &[Vec2], iterations: u32) -> Vec<Vec2> {
+    let mut points = vertices.to_vec();
+    for _ in 0..iterations {
+        if points.len() < 3 {
+            break;
+        }
+        let mut smoothed = Vec::with_capacity(points.len() * 2);
+        smoothed.push(points[0]);
+        for w in points.windows(2) {
+            smoothed.push(w[0].lerp(w[1], 0.25));
+            smoothed.push(w[0].lerp(w[1], 0.75));
+        }
+        smoothed.push(points[points.len() - 1]);
+        points = smoothed;
+    }
+    points
+}
+
+pub fn smooth_catmull_rom(vertices: &[Vec2], samples: u32) -> Vec<Vec2> {
+    if vertices.len() < 3 || samples < 2 {
+        return vertices.to_vec();
+    }
+    let last = vertices.len() - 1;
+    let mut smoothed = Vec::with_capacity(last * samples as usize + 1);
+    for i in 0..last {
+        let p0 = vertices[i.saturating_sub(1)];
+        let p1 = vertices[i];
+        let p2 = vertices[i + 1];
+        let p3 = vertices[(i + 2).min(last)];
+        for s in 0..samples {
+            let t = s as f32 / samples as f32;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            smoothed.push(0.5 * (
+                2. * p1
+                + (p2 - p0) * t
+                + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+                + (3. * p1 - p0 - 3. * p2 + p3) * t3
+            ));
+        }
+    }
+    smoothed.push(vertices[last]);
+    smoothed
+}
+
+// parameters `t` of `p + t * d` with `lo <= alpha + beta * t <= hi`
+fn linear_range(alpha: f32, beta: f32, lo: f32, hi: f32) -> Option<(f32, f32)> {
+    if beta.abs() <= f32::EPSILON {
+        return (lo..=hi).contains(&alpha).then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+    let (t0, t1) = ((lo - alpha) / beta, (hi - alpha) / beta);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// parameters `t` of `p + t * d` within `radius` of `center`
+fn circle_range(p: Vec2, d: Vec2, center: Vec2, radius: f32) -> Option<(f32, f32)> {
+    let offset = p - center;
+    let a = d.length_squared();
+    let c = offset.length_squared() - radius * radius;
+    if a <= f32::EPSILON {
+        return (c <= 0.).then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+    let b = d.dot(offset);
+    let discriminant = b * b - a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some(((-b - root) / a, (-b + root) / a))
+}
+
+// parameter range of the segment `p`-`q` lying within `radius` of the segment `a`-`b`
+fn segment_capsule_overlap(p: Vec2, q: Vec2, a: Vec2, b: Vec2, radius: f32) -> Option<(f32, f32)> {
+    let d = q - p;
+    // the capsule is the two end circles and the strip between them, it's convex so the line
+    // crosses their union in a single interval
+    let axis = b - a;
+    let length = axis.length();
+    let strip = (length > f32::EPSILON).then(|| {
+        let (along, across) = (axis / length, axis.perp() / length);
+        linear_range((p - a).dot(along), d.dot(along), 0., length)
+            .zip(linear_range((p - a).dot(across), d.dot(across), -radius, radius))
+            .map(|((s0, s1), (t0, t1))| (s0.max(t0), s1.min(t1)))
+            .filter(|(t0, t1)| t0 <= t1)
+    }).flatten();
+    let (t0, t1) = [circle_range(p, d, a, radius), circle_range(p, d, b, radius), strip].into_iter().flatten()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(t0, t1), (r0, r1)| (t0.min(r0), t1.max(r1)));
+    let (t0, t1) = (t0.max(0.), t1.min(1.));
+    (t0 <= t1).then_some((t0, t1))
+}
+
+/// Cuts away the parts of a polyline within `radius` of the segment `a`-`b`, the eraser's path
+/// since the last frame.
+///
+/// Returns `None` when the polyline doesn't touch it, otherwise the pieces left over, ending
+/// exactly where they cross the eraser.
+pub fn cut_polyline(vertices: &[Vec2], a: Vec2, b: Vec2, radius: f32) -> Option<Vec<Vec<Vec2>>> {
+    if vertices.len() == 1 {
+        return (distance_to_segment(vertices[0], a, b) <= radius).then(Vec::new);
+    }
+    let mut touched = false;
+    let mut pieces = Vec::new();
+    let mut current = Vec::new();
+    if let Some(first) = vertices.first() {
+        if distance_to_segment(*first, a, b) > radius {
+            current.push(*first);
+        }
+    }
+    for w in vertices.windows(2) {
+        let (p, q) = (w[0], w[1]);
+        let Some((t0, t1)) = segment_capsule_overlap(p, q, a, b, radius) else {
+            current.push(q);
+            continue;
+        };
+        touched = true;
+        if t0 > 0. {
+            current.push(p.lerp(q, t0));
+        }
+        pieces.push(std::mem::take(&mut current));
+        if t1 < 1. {
+            current.push(p.lerp(q, t1));
+            current.push(q);
+        }
+    }
+    if !touched {
+        return None;
+    }
+    pieces.push(current);
+    pieces.retain(|piece| piece.len() > 1 && piece.windows(2).any(|w| w[0] != w[1]));
+    Some(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_pieces(pieces: &[Vec<Vec2>], expected: &[&[Vec2]]) {
+        assert_eq!(pieces.len(), expected.len(), "{:?}", pieces);
+        for (piece, expected) in pieces.iter().zip(expected) {
+            assert_eq!(piece.len(), expected.len(), "{:?}", pieces);
+            for (v, e) in piece.iter().zip(expected.iter()) {
+                assert!(v.abs_diff_eq(*e, 1e-3), "{:?} isn't {:?}", pieces, expected);
+            }
+        }
+    }
+
+    fn zigzag() -> Vec<Vec2> {
+        vec![Vec2::new(0., 0.), Vec2::new(10., 10.), Vec2::new(20., 0.), Vec2::new(30., 10.), Vec2::new(40., 0.)]
+    }
+
+    #[test]
+    fn simplify_keeps_the_endpoints() {
+        let vertices = zigzag();
+        // a tolerance wider than the zigzag drops everything in between
+        assert_eq!(simplify_rdp(&vertices, 20.), vec![vertices[0], vertices[4]]);
+        let simplified = simplify_rdp(&vertices, 1.);
+        assert_eq!((simplified.first(), simplified.last()), (vertices.first(), vertices.last()));
+    }
+
+    #[test]
+    fn simplify_collapses_collinear_points() {
+        let vertices = [Vec2::new(0., 0.), Vec2::new(1., 0.), Vec2::new(2., 0.), Vec2::new(3., 0.)];
+        assert_eq!(simplify_rdp(&vertices, 0.5), vec![vertices[0], vertices[3]]);
+    }
+
+    #[test]
+    fn fewer_than_three_points_pass_through() {
+        let vertices = [Vec2::new(0., 0.), Vec2::new(10., 5.)];
+        assert_eq!(simplify_rdp(&vertices, 1.), vertices);
+        assert_eq!(smooth_chaikin(&vertices, 2), vertices);
+        assert_eq!(smooth_catmull_rom(&vertices, 8), vertices);
+        assert_eq!(simplify_rdp(&vertices[..1], 1.), &vertices[..1]);
+    }
+
+    #[test]
+    fn chaikin_doubles_the_points_each_iteration() {
+        let vertices = zigzag();
+        assert_eq!(smooth_chaikin(&vertices, 1).len(), 10);
+        assert_eq!(smooth_chaikin(&vertices, 2).len(), 20);
+        let smoothed = smooth_chaikin(&vertices, 3);
+        assert_eq!(smoothed.len(), 40);
+        assert_eq!((smoothed.first(), smoothed.last()), (vertices.first(), vertices.last()));
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_the_control_points() {
+        let vertices = zigzag();
+        let samples = 8;
+        let smoothed = smooth_catmull_rom(&vertices, samples);
+        assert_eq!(smoothed.len(), (vertices.len() - 1) * samples as usize + 1);
+        for (i, v) in vertices.iter().enumerate() {
+            assert!(smoothed[i * samples as usize].abs_diff_eq(*v, 1e-4), "{:?} isn't {:?}", smoothed[i * samples as usize], v);
+        }
+    }
+
+    #[test]
+    fn fast_swipe_cuts_a_long_segment_in_two() {
+        let line = [Vec2::new(-1000., 0.), Vec2::new(1000., 0.)];
+        // the whole swipe in one frame, far past the line on both sides
+        let pieces = cut_polyline(&line, Vec2::new(0., -500.), Vec2::new(0., 500.), 10.).unwrap();
+        assert_pieces(&pieces, &[
+            &[Vec2::new(-1000., 0.), Vec2::new(-10., 0.)],
+            &[Vec2::new(10., 0.), Vec2::new(1000., 0.)],
+        ]);
+    }
+
+    #[test]
+    fn cut_at_the_start_keeps_the_rest() {
+        let line = [Vec2::new(0., 0.), Vec2::new(100., 0.), Vec2::new(200., 0.)];
+        let pieces = cut_polyline(&line, Vec2::new(0., -50.), Vec2::new(0., 50.), 10.).unwrap();
+        assert_pieces(&pieces, &[&[Vec2::new(10., 0.), Vec2::new(100., 0.), Vec2::new(200., 0.)]]);
+    }
+
+    #[test]
+    fn cut_at_the_end_keeps_the_rest() {
+        let line = [Vec2::new(0., 0.), Vec2::new(100., 0.), Vec2::new(200., 0.)];
+        let pieces = cut_polyline(&line, Vec2::new(200., -50.), Vec2::new(200., 50.), 10.).unwrap();
+        assert_pieces(&pieces, &[&[Vec2::new(0., 0.), Vec2::new(100., 0.), Vec2::new(190., 0.)]]);
+    }
+
+    #[test]
+    fn eraser_tip_cuts_where_its_circle_crosses() {
+        // the swipe stops short of the line, only its round end reaches it
+        let line = [Vec2::new(-100., 0.), Vec2::new(100., 0.)];
+        let pieces = cut_polyline(&line, Vec2::new(0., -50.), Vec2::new(0., -6.), 10.).unwrap();
+        assert_pieces(&pieces, &[
+            &[Vec2::new(-100., 0.), Vec2::new(-8., 0.)],
+            &[Vec2::new(8., 0.), Vec2::new(100., 0.)],
+        ]);
+    }
+
+    #[test]
+    fn eraser_passing_by_leaves_the_line() {
+        let line = [Vec2::new(-100., 0.), Vec2::new(100., 0.)];
+        assert_eq!(cut_polyline(&line, Vec2::new(-100., 20.), Vec2::new(100., 20.), 10.), None);
+    }
+}
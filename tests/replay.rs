@@ -0,0 +1,112 @@
+//! Recording runs and playing them back, see `moon::replay`.
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use moon::{AppConfig, ink::Ink, input::DrawInput, level::Level, player::Lines, replay::{Replay, ReplayRecorder}, sim::Simulation};
+
+fn level() -> Level {
+    // the star is out of reach, the level keeps going
+    Level { player: Vec2::new(0., 100.), stars: vec![Vec2::new(500., 300.)], ..default() }
+}
+
+fn ticks(sim: &Simulation) -> u32 {
+    sim.app.world.resource::<ReplayRecorder>().tick
+}
+
+#[test]
+fn replay_text_round_trips() {
+    let text = "v 1\nf level-0.txt\n3 b -200,-20.5\n4 e 0,-40\n5 u\n9 x 1,2\n10 xu\n11 z\n11 zr\n12 g\n40 rc\n";
+    let replay = Replay::parse(text).expect("valid replay");
+    assert_eq!(replay.level, "level-0.txt");
+    assert_eq!(replay.inputs.len(), 9);
+    assert_eq!(replay.to_text(), text);
+}
+
+#[test]
+fn replay_rejects_unknown_inputs() {
+    let error = Replay::parse("v 1\n3 q 1,2\n").unwrap_err();
+    assert_eq!((error.line, error.token.as_str()), (2, "q"));
+}
+
+fn draw_line(sim: &mut Simulation, slope: f32) {
+    sim.draw(DrawInput::Begin(Vec2::new(-200., 0.)));
+    for x in [-100., 0., 100., 200.] {
+        sim.tick();
+        sim.draw(DrawInput::Extend(Vec2::new(x, -x * slope)));
+    }
+    sim.tick();
+    sim.draw(DrawInput::End);
+    sim.tick();
+}
+
+fn line_vertices(sim: &Simulation) -> Vec<Vec<Vec2>> {
+    let mut lines = sim.app.world.resource::<Lines>().0.values().cloned().collect::<Vec<_>>();
+    lines.sort_by(|a, b| a[0].x.total_cmp(&b[0].x).then(a[0].y.total_cmp(&b[0].y)));
+    lines
+}
+
+fn record_run() -> Simulation {
+    let mut sim = Simulation::new(vec![level()]);
+    sim.wait_for_playing(600).expect("level never started");
+    draw_line(&mut sim, 0.2);
+    sim.release_gravity();
+    for _ in 0..120 {
+        sim.tick();
+    }
+    sim
+}
+
+#[test]
+fn replay_reproduces_the_run() {
+    let mut sim = record_run();
+    let recorded = Replay::parse(&sim.recording().to_text()).expect("recording reads back");
+    assert_eq!(&recorded, sim.recording());
+
+    let mut replayed = Simulation::new(vec![level()]);
+    replayed.queue_replay(recorded);
+    replayed.wait_for_playing(600).expect("level never started");
+    replayed.run_until(600, |replayed| ticks(replayed) == ticks(&sim)).expect("replay fell behind");
+    assert_eq!(replayed.recording(), sim.recording());
+    assert_eq!(replayed.player_position(), sim.player_position());
+}
+
+#[test]
+fn replay_reproduces_the_run_at_another_frame_rate() {
+    let mut sim = record_run();
+    let mut config = AppConfig::headless(vec![level()]);
+    // a 144Hz monitor, most updates take no step
+    config.fixed_timestep = Some(Duration::from_secs_f32(1. / 144.));
+    let mut replayed = Simulation::with_config(config);
+    replayed.queue_replay(sim.recording().clone());
+    replayed.wait_for_playing(2000).expect("level never started");
+    replayed.run_until(2000, |replayed| ticks(replayed) == ticks(&sim)).expect("replay fell behind");
+    assert_eq!(replayed.recording(), sim.recording());
+    assert_eq!(replayed.player_position(), sim.player_position());
+}
+
+#[test]
+fn replay_reproduces_undo() {
+    let mut sim = Simulation::new(vec![level()]);
+    sim.wait_for_playing(600).expect("level never started");
+    draw_line(&mut sim, 0.2);
+    sim.draw(DrawInput::Undo);
+    sim.tick();
+    assert!(line_vertices(&sim).is_empty(), "undo kept the line");
+    draw_line(&mut sim, -0.2);
+    sim.release_gravity();
+    for _ in 0..120 {
+        sim.tick();
+    }
+    let recorded = Replay::parse(&sim.recording().to_text()).expect("recording reads back");
+    assert_eq!(&recorded, sim.recording());
+
+    let mut replayed = Simulation::new(vec![level()]);
+    replayed.queue_replay(recorded);
+    replayed.wait_for_playing(600).expect("level never started");
+    replayed.run_until(600, |replayed| ticks(replayed) == ticks(&sim)).expect("replay fell behind");
+    assert_eq!(replayed.recording(), sim.recording());
+    assert_eq!(line_vertices(&replayed), line_vertices(&sim));
+    assert_eq!(line_vertices(&sim).len(), 1);
+    assert_eq!(replayed.app.world.resource::<Ink>().used, sim.app.world.resource::<Ink>().used);
+    assert_eq!(replayed.player_position(), sim.player_position());
+}
@@ -0,0 +1,106 @@
+//! Whole levels played headless, see `moon::sim::Simulation`.
+
+use bevy::prelude::*;
+use moon::{GameState, ink::Ink, input::DrawInput, level::Level, player::Lines, progress::Progress, sim::Simulation};
+
+// a star somewhere the ball never goes, so the level can't end by itself
+const OUT_OF_REACH: Vec2 = Vec2::new(500., 300.);
+
+fn level(player: Vec2, stars: Vec<Vec2>) -> Level {
+    Level { player, stars, ..default() }
+}
+
+#[test]
+fn star_below_spawn_is_collected() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![Vec2::new(0., -100.)])]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.release_gravity();
+    sim.run_until(300, |sim| sim.stars_left() == 0).expect("star not collected");
+    sim.run_until(10, |sim| sim.state() == GameState::Cleaning).expect("level not finished");
+}
+
+#[test]
+fn last_star_wins_over_a_hazard_on_the_same_step() {
+    // the ball starts on both, the first step reaches them together
+    let level = Level {
+        hazards: vec![vec![Vec2::new(-50., 100.), Vec2::new(50., 100.)]],
+        ..level(Vec2::new(0., 100.), vec![Vec2::new(0., 100.)])
+    };
+    let mut sim = Simulation::new(vec![level]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.run_until(10, |sim| sim.state() != GameState::Playing).expect("neither star nor hazard touched");
+    assert_eq!(sim.state(), GameState::Cleaning);
+}
+
+#[test]
+fn ball_stays_still_until_released() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![OUT_OF_REACH])]);
+    sim.wait_for_playing(600).expect("level never started");
+    for _ in 0..60 {
+        sim.tick();
+    }
+    assert_eq!(sim.player_position(), Some(Vec2::new(0., 100.)));
+}
+
+#[test]
+fn drawn_line_holds_the_ball() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![OUT_OF_REACH])]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.add_line(&[Vec2::new(-200., 0.), Vec2::new(200., 0.)]);
+    sim.release_gravity();
+    for _ in 0..120 {
+        sim.tick();
+    }
+    assert_eq!(sim.state(), GameState::Playing);
+    let position = sim.player_position().expect("no ball");
+    assert!(position.y > 0., "ball fell through the line to {:?}", position);
+}
+
+#[test]
+fn line_stops_where_the_ink_runs_out() {
+    let level = Level { ink: Some(100.), ..level(Vec2::new(0., 100.), vec![OUT_OF_REACH]) };
+    let mut sim = Simulation::new(vec![level]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.draw(DrawInput::Begin(Vec2::new(0., 0.)));
+    sim.tick();
+    sim.draw(DrawInput::Extend(Vec2::new(150., 0.)));
+    sim.tick();
+    let lines = sim.app.world.resource::<Lines>();
+    let line = lines.0.values().next().expect("no line drawn");
+    assert_eq!(line, &vec![Vec2::new(0., 0.), Vec2::new(100., 0.)]);
+    assert_eq!(sim.app.world.resource::<Ink>().remaining(), 0.);
+}
+
+#[test]
+fn empty_line_is_ignored() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![OUT_OF_REACH])]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.add_line(&[]);
+    sim.tick();
+    assert_eq!(sim.state(), GameState::Playing);
+}
+
+#[test]
+fn ball_falling_out_of_the_level_is_retried() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![OUT_OF_REACH])]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.release_gravity();
+    sim.run_until(300, |sim| {
+        sim.app.world.resource::<Progress>().levels.values().any(|record| record.attempts == 2)
+    }).expect("ball never lost");
+    sim.wait_for_playing(10).expect("level not retried");
+    assert_eq!(sim.player_position(), Some(Vec2::new(0., 100.)));
+}
+
+#[test]
+fn finished_run_is_kept_as_ghost() {
+    let mut sim = Simulation::new(vec![level(Vec2::new(0., 100.), vec![Vec2::new(0., -100.)])]);
+    sim.wait_for_playing(600).expect("level never started");
+    sim.release_gravity();
+    sim.run_until(300, |sim| sim.state() == GameState::Cleaning).expect("level not finished");
+    let progress = sim.app.world.resource::<Progress>();
+    let ghost = progress.ghosts.values().next().expect("no ghost kept");
+    assert_eq!(ghost.strokes, 0);
+    assert_eq!(ghost.position_at(0), Some(Vec2::new(0., 100.)));
+    assert!(ghost.trajectory.last().is_some_and(|(_, p)| p.y < 0.), "ghost never fell: {:?}", ghost.trajectory.last());
+}